@@ -0,0 +1,604 @@
+use crate::{compare_versions, download, paths};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Portable programm: archive extracted into `apps/<name>/<version>` with shims in managed bin dir
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ArchiveSpec {
+    pub url: String,
+    pub sha256: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<String>,
+    pub version: String,
+    /// Executables inside the archive to create shims for, relative to its root
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bin: Vec<String>,
+}
+
+/// What was installed, so uninstallation removes exactly these files
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct AppManifest {
+    current: Option<String>,
+    versions: BTreeMap<String, InstalledVersion>,
+    /// Shims of the current version
+    shims: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct InstalledVersion {
+    bin: Vec<String>,
+    files: Vec<PathBuf>,
+    dirs: Vec<PathBuf>,
+}
+
+/// Shim file name to the app it belongs to, kept next to the app directories
+type ShimOwners = BTreeMap<String, String>;
+
+/// Held while the shim owners are read and written back, archive jobs may run in parallel
+static SHIM_OWNERS: Mutex<()> = Mutex::new(());
+
+/// Where an app is extracted and where its shims go
+struct App {
+    /// Directory name, owner of the shims
+    name: String,
+    dir: PathBuf,
+    bin_dir: PathBuf,
+}
+
+impl App {
+    fn new(name: &str) -> App {
+        App::at(&paths::apps_dir(), &paths::bin_dir(), name)
+    }
+
+    fn at(apps_dir: &Path, bin_dir: &Path, name: &str) -> App {
+        let name = name.replace(['/', '\\', ':'], "_");
+        App {
+            dir: apps_dir.join(&name),
+            bin_dir: bin_dir.to_path_buf(),
+            name,
+        }
+    }
+
+    fn owners_path(&self) -> PathBuf {
+        self.dir
+            .parent()
+            .unwrap_or(&self.dir)
+            .join("shim-owners.json")
+    }
+}
+
+fn load_manifest(app_dir: &Path) -> AppManifest {
+    fs::read(app_dir.join("manifest.json"))
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_manifest(app_dir: &Path, manifest: &AppManifest) -> Result<(), String> {
+    let data = serde_json::to_vec_pretty(manifest).map_err(|e| e.to_string())?;
+    fs::write(app_dir.join("manifest.json"), data).map_err(|e| e.to_string())
+}
+
+impl ArchiveSpec {
+    /// Archive itself as a download
+    pub fn source(&self) -> download::DownloadSpec {
+        download::DownloadSpec {
+            url: self.url.clone(),
+            sha256: self.sha256.clone(),
+            mirrors: self.mirrors.clone(),
+            args: Vec::new(),
+            file_name: None,
+            policy: Default::default(),
+        }
+    }
+
+    /// Version names the directory it's extracted to, so it must be a plain name, and
+    /// `bin` entries must stay inside it
+    pub fn check(&self) -> Result<(), String> {
+        if !paths::is_file_name(&self.version) || self.version.starts_with('.') {
+            return Err(format!(
+                "Version \"{}\" can't be used as a directory name",
+                self.version
+            ));
+        }
+        for exe in &self.bin {
+            let inside = Path::new(exe)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+            if exe.trim().is_empty() || !inside {
+                return Err(format!(
+                    "Bin entry \"{}\" isn't a path inside the archive",
+                    exe
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Version the shims point to, `None` when nothing is installed
+pub fn current_version(name: &str) -> Option<String> {
+    load_manifest(&App::new(name).dir).current
+}
+
+/// Versions recorded in the manifest, oldest first
+pub fn installed_versions(name: &str) -> Vec<String> {
+    let mut versions: Vec<String> = load_manifest(&App::new(name).dir)
+        .versions
+        .into_keys()
+        .collect();
+    versions.sort_by(|a, b| compare_versions(a, b));
+    versions
+}
+
+/// Directory the given version is extracted to
+pub fn install_dir(name: &str, version: &str) -> PathBuf {
+    App::new(name).dir.join(version)
+}
+
+pub fn install(
+    name: &str,
+    spec: &ArchiveSpec,
+    progress: &mut impl FnMut(u64, Option<u64>),
+) -> Result<(), String> {
+    spec.check()?;
+    let archive = download::fetch(&spec.source(), &paths::cache_dir(), progress)?;
+    install_archive(&App::new(name), spec, &archive)
+}
+
+fn install_archive(app: &App, spec: &ArchiveSpec, archive: &Path) -> Result<(), String> {
+    let app_dir = &app.dir;
+    let version_dir = app_dir.join(&spec.version);
+    let mut manifest = load_manifest(app_dir);
+    let previous = manifest.current.clone();
+    let staging = app_dir.join(format!(".{}.tmp", spec.version));
+    let _ = fs::remove_dir_all(&staging);
+    fs::create_dir_all(&staging).map_err(|e| e.to_string())?;
+    if let Err(e) = extract(archive, &staging) {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(format!("Can't extract {}: {}", archive.display(), e));
+    }
+    // Most archives keep everything in a single `tool-1.2.3/` folder, it's dropped
+    let root = single_subdir(&staging).unwrap_or_else(|| staging.clone());
+    // Installed files of the same version are replaced only now that the new ones are
+    // there, and kept aside until the new version is registered
+    let backup = app_dir.join(format!(".{}.old", spec.version));
+    let _ = fs::remove_dir_all(&backup);
+    let replacing = version_dir.exists();
+    let swapped = if replacing {
+        fs::rename(&version_dir, &backup)
+    } else {
+        Ok(())
+    }
+    .and_then(|()| fs::rename(&root, &version_dir));
+    let _ = fs::remove_dir_all(&staging);
+    if let Err(e) = swapped {
+        if replacing && !version_dir.exists() {
+            let _ = fs::rename(&backup, &version_dir);
+        }
+        return Err(e.to_string());
+    }
+    let replaced = manifest.versions.remove(&spec.version);
+
+    let mut installed = InstalledVersion {
+        bin: spec.bin.clone(),
+        ..Default::default()
+    };
+    let registered = walk(&version_dir, &mut installed)
+        .map_err(|e| e.to_string())
+        .and_then(|()| {
+            manifest.versions.insert(spec.version.clone(), installed);
+            set_current(app, &mut manifest, &spec.version)?;
+            save_manifest(app_dir, &manifest)
+        });
+    if let Err(e) = registered {
+        match replaced {
+            // Files of the same version are put back as they were
+            Some(replaced) if replacing => {
+                remove_shims(app, &mut manifest);
+                manifest.current = None;
+                let _ = fs::remove_dir_all(&version_dir);
+                let _ = fs::rename(&backup, &version_dir);
+                manifest.versions.insert(spec.version.clone(), replaced);
+                if let Some(previous) = &previous {
+                    let _ = set_current(app, &mut manifest, previous);
+                }
+                let _ = save_manifest(app_dir, &manifest);
+            }
+            // Half registered version is dropped, so the previous one keeps working
+            _ => {
+                let previous = previous.filter(|v| *v != spec.version);
+                let _ = restore(app, &mut manifest, &spec.version, previous.as_deref());
+            }
+        }
+        let _ = fs::remove_dir_all(&backup);
+        return Err(e);
+    }
+    let _ = fs::remove_dir_all(&backup);
+    Ok(())
+}
+
+/// Removes the just installed version and makes `previous` current again
+pub fn discard(name: &str, version: &str, previous: Option<&str>) -> Result<(), String> {
+    let app = App::new(name);
+    let mut manifest = load_manifest(&app.dir);
+    restore(&app, &mut manifest, version, previous)
+}
+
+fn restore(
+    app: &App,
+    manifest: &mut AppManifest,
+    version: &str,
+    previous: Option<&str>,
+) -> Result<(), String> {
+    remove_version(manifest, version);
+    // Directory is fresh, everything inside came from the archive
+    let _ = fs::remove_dir_all(app.dir.join(version));
+    if manifest.current.as_deref() != previous {
+        remove_shims(app, manifest);
+        manifest.current = None;
+    }
+    if let Some(previous) = previous.filter(|v| manifest.versions.contains_key(*v)) {
+        set_current(app, manifest, previous)?;
+    }
+    store_manifest(&app.dir, manifest)
+}
+
+/// Removes the files and shims of every installed version, whatever the catalog says now
+pub fn uninstall(name: &str) -> Result<(), String> {
+    uninstall_app(&App::new(name), name)
+}
+
+fn uninstall_app(app: &App, name: &str) -> Result<(), String> {
+    let mut manifest = load_manifest(&app.dir);
+    if manifest.versions.is_empty() {
+        return Err(format!("{} isn't installed", name));
+    }
+    remove_shims(app, &mut manifest);
+    manifest.current = None;
+    let versions: Vec<String> = manifest.versions.keys().cloned().collect();
+    for version in versions {
+        remove_version(&mut manifest, &version);
+    }
+    store_manifest(&app.dir, &manifest)
+}
+
+/// Saves the manifest, app directory is removed with the last version
+fn store_manifest(app_dir: &Path, manifest: &AppManifest) -> Result<(), String> {
+    if manifest.versions.is_empty() {
+        let _ = fs::remove_file(app_dir.join("manifest.json"));
+        let _ = fs::remove_dir(app_dir);
+        Ok(())
+    } else {
+        save_manifest(app_dir, manifest)
+    }
+}
+
+fn remove_version(manifest: &mut AppManifest, version: &str) {
+    if let Some(installed) = manifest.versions.remove(version) {
+        for file in &installed.files {
+            let _ = fs::remove_file(file);
+        }
+        // Deepest first, directories with files we didn't create are kept
+        for dir in installed.dirs.iter().rev() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+}
+
+fn set_current(app: &App, manifest: &mut AppManifest, version: &str) -> Result<(), String> {
+    remove_shims(app, manifest);
+    manifest.current = None;
+    fs::create_dir_all(&app.bin_dir).map_err(|e| e.to_string())?;
+    let bin = manifest
+        .versions
+        .get(version)
+        .map(|v| v.bin.clone())
+        .unwrap_or_default();
+    let version_dir = app.dir.join(version);
+    for exe in bin {
+        let shim = create_shim(app, &version_dir.join(&exe))?;
+        manifest.shims.push(shim);
+    }
+    manifest.current = Some(version.to_string());
+    Ok(())
+}
+
+fn load_owners(app: &App) -> ShimOwners {
+    fs::read(app.owners_path())
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_owners(app: &App, owners: &ShimOwners) -> Result<(), String> {
+    let path = app.owners_path();
+    let data = serde_json::to_vec_pretty(owners).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("Can't write {}: {}", path.display(), e))
+}
+
+fn shim_key(shim: &Path) -> String {
+    shim.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Shim of another app or a file the user put there is never replaced
+fn create_shim(app: &App, target: &Path) -> Result<PathBuf, String> {
+    let shim = shim_path(&app.bin_dir, target);
+    let _owners_lock = SHIM_OWNERS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut owners = load_owners(app);
+    let key = shim_key(&shim);
+    match owners.get(&key) {
+        Some(owner) if *owner != app.name => {
+            return Err(format!("{} is a shim of {}", shim.display(), owner));
+        }
+        None if shim.symlink_metadata().is_ok() => {
+            return Err(format!("{} already exists", shim.display()));
+        }
+        _ => {}
+    }
+    write_shim(&shim, target)
+        .map_err(|e| format!("Can't create shim for {}: {}", target.display(), e))?;
+    owners.insert(key, app.name.clone());
+    save_owners(app, &owners)?;
+    Ok(shim)
+}
+
+/// Shims taken over by another app stay
+fn remove_shims(app: &App, manifest: &mut AppManifest) {
+    if manifest.shims.is_empty() {
+        return;
+    }
+    let _owners_lock = SHIM_OWNERS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut owners = load_owners(app);
+    for shim in manifest.shims.drain(..) {
+        let key = shim_key(&shim);
+        if owners.get(&key).is_some_and(|owner| *owner != app.name) {
+            continue;
+        }
+        let _ = fs::remove_file(&shim);
+        owners.remove(&key);
+    }
+    let _ = save_owners(app, &owners);
+}
+
+#[cfg(unix)]
+fn shim_path(bin_dir: &Path, target: &Path) -> PathBuf {
+    bin_dir.join(target.file_name().unwrap_or_default())
+}
+
+#[cfg(unix)]
+fn write_shim(shim: &Path, target: &Path) -> io::Result<()> {
+    let _ = fs::remove_file(shim);
+    std::os::unix::fs::symlink(target, shim)
+}
+
+/// Symlinks need admin rights on Windows, so shim is a `.cmd` forwarding all arguments
+#[cfg(not(unix))]
+fn shim_path(bin_dir: &Path, target: &Path) -> PathBuf {
+    bin_dir
+        .join(target.file_stem().unwrap_or_default())
+        .with_extension("cmd")
+}
+
+#[cfg(not(unix))]
+fn write_shim(shim: &Path, target: &Path) -> io::Result<()> {
+    fs::write(shim, format!("@\"{}\" %*\r\n", target.display()))
+}
+
+fn single_subdir(dir: &Path) -> Option<PathBuf> {
+    let mut entries = fs::read_dir(dir).ok()?;
+    let entry = entries.next()?.ok()?;
+    (entries.next().is_none() && entry.file_type().ok()?.is_dir()).then(|| entry.path())
+}
+
+fn walk(dir: &Path, installed: &mut InstalledVersion) -> io::Result<()> {
+    installed.dirs.push(dir.to_path_buf());
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            walk(&entry.path(), installed)?;
+        } else {
+            installed.files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+fn extract(archive: &Path, dest: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let name = archive
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    let file = BufReader::new(File::open(archive)?);
+    if name.ends_with(".zip") {
+        zip::ZipArchive::new(file)?.extract(dest)?;
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        tar::Archive::new(flate2::read::GzDecoder::new(file)).unpack(dest)?;
+    } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+        // Unpacked tar is too big to keep in memory, it goes next to the archive
+        let tar_path = archive.with_extension("unpacked");
+        let mut file = file;
+        lzma_rs::xz_decompress(&mut file, &mut File::create(&tar_path)?)?;
+        let unpacked = tar::Archive::new(File::open(&tar_path)?).unpack(dest);
+        let _ = fs::remove_file(&tar_path);
+        unpacked?;
+    } else if name.ends_with(".tar") {
+        tar::Archive::new(file).unpack(dest)?;
+    } else {
+        return Err(format!("Unsupported archive type: {}", name).into());
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{env, io::Write, process};
+
+    /// Files of a test archive, all inside `tool-<version>/`
+    fn files(version: &str) -> Vec<(String, String)> {
+        vec![
+            (
+                format!("tool-{}/bin/tool", version),
+                format!("tool {}", version),
+            ),
+            (format!("tool-{}/README", version), "readme".to_string()),
+        ]
+    }
+
+    fn zip_archive(path: &Path, files: &[(String, String)]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in files {
+            zip.start_file(name.as_str(), zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(data.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn tar_gz_archive(path: &Path, files: &[(String, String)]) {
+        let gz = flate2::write::GzEncoder::new(
+            File::create(path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut tar = tar::Builder::new(gz);
+        for (name, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            tar.append_data(&mut header, name, data.as_bytes()).unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+    }
+
+    /// Fresh apps and bin directories of the test
+    fn root(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("wtb-archive-{}-{}", test, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn app(root: &Path, name: &str) -> App {
+        App::at(&root.join("apps"), &root.join("bin"), name)
+    }
+
+    fn spec(version: &str) -> ArchiveSpec {
+        ArchiveSpec {
+            url: String::new(),
+            sha256: String::new(),
+            mirrors: Vec::new(),
+            version: version.to_string(),
+            bin: vec!["bin/tool".to_string()],
+        }
+    }
+
+    fn install_version(root: &Path, app: &App, version: &str) {
+        let archive = root.join(format!("tool-{}.zip", version));
+        zip_archive(&archive, &files(version));
+        install_archive(app, &spec(version), &archive).unwrap();
+    }
+
+    fn shim_target(app: &App) -> PathBuf {
+        fs::read_link(app.bin_dir.join("tool")).unwrap()
+    }
+
+    #[test]
+    fn zip_and_tar_gz_are_extracted() {
+        type Writer = fn(&Path, &[(String, String)]);
+        let writers: [(&str, Writer); 2] = [("zip", zip_archive), ("tar.gz", tar_gz_archive)];
+        for (extension, write) in writers {
+            let root = root(&format!("extract-{}", extension));
+            let archive = root.join(format!("tool.{}", extension));
+            write(&archive, &files("1.0"));
+            let app = app(&root, "tool");
+            install_archive(&app, &spec("1.0"), &archive).unwrap();
+            let version_dir = app.dir.join("1.0");
+            let tool = fs::read_to_string(version_dir.join("bin/tool")).unwrap();
+            assert_eq!(tool, "tool 1.0", "{}", extension);
+            assert!(version_dir.join("README").is_file());
+            assert_eq!(load_manifest(&app.dir).current.as_deref(), Some("1.0"));
+            assert_eq!(shim_target(&app), version_dir.join("bin/tool"));
+        }
+    }
+
+    #[test]
+    fn versions_coexist_and_switch() {
+        let root = root("versions");
+        let app = app(&root, "tool");
+        install_version(&root, &app, "1.0");
+        install_version(&root, &app, "2.0");
+        assert!(app.dir.join("1.0/bin/tool").is_file());
+        assert!(app.dir.join("2.0/bin/tool").is_file());
+        assert_eq!(shim_target(&app), app.dir.join("2.0/bin/tool"));
+
+        let mut manifest = load_manifest(&app.dir);
+        restore(&app, &mut manifest, "2.0", Some("1.0")).unwrap();
+        let manifest = load_manifest(&app.dir);
+        assert_eq!(manifest.current.as_deref(), Some("1.0"));
+        assert_eq!(manifest.versions.keys().collect::<Vec<_>>(), vec!["1.0"]);
+        assert!(!app.dir.join("2.0").exists());
+        assert_eq!(shim_target(&app), app.dir.join("1.0/bin/tool"));
+    }
+
+    #[test]
+    fn uninstall_removes_exactly_the_manifest_files() {
+        let root = root("uninstall");
+        let app = app(&root, "tool");
+        install_version(&root, &app, "1.0");
+        install_version(&root, &app, "2.0");
+        let user_file = app.dir.join("1.0/bin/settings.ini");
+        fs::write(&user_file, "mine").unwrap();
+
+        uninstall_app(&app, "tool").unwrap();
+        assert!(user_file.is_file());
+        assert!(!app.dir.join("1.0/README").exists());
+        assert!(!app.dir.join("2.0").exists());
+        assert!(!app.dir.join("manifest.json").exists());
+        assert!(app.bin_dir.join("tool").symlink_metadata().is_err());
+        assert!(uninstall_app(&app, "tool").is_err());
+    }
+
+    #[test]
+    fn shims_of_other_apps_are_kept() {
+        let root = root("owners");
+        let first = app(&root, "first");
+        install_version(&root, &first, "1.0");
+        let second = app(&root, "second");
+        let archive = root.join("second.zip");
+        zip_archive(&archive, &files("1.0"));
+        let error = install_archive(&second, &spec("1.0"), &archive).unwrap_err();
+        assert!(error.contains("is a shim of first"), "{}", error);
+        assert!(load_manifest(&second.dir).versions.is_empty());
+        assert_eq!(shim_target(&first), first.dir.join("1.0/bin/tool"));
+
+        // Shim listed in the manifest of another app isn't removed with it
+        let mut manifest = load_manifest(&first.dir);
+        remove_shims(&second, &mut manifest);
+        assert_eq!(shim_target(&first), first.dir.join("1.0/bin/tool"));
+    }
+
+    #[test]
+    fn bin_must_stay_inside_the_archive() {
+        for bin in ["../x", "/usr/bin/x", "bin/../../x", ""] {
+            let spec = ArchiveSpec {
+                bin: vec![bin.to_string()],
+                ..spec("1.0")
+            };
+            assert!(spec.check().is_err(), "{}", bin);
+        }
+        assert!(spec("1.0").check().is_ok());
+        assert!(spec("../1.0").check().is_err());
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{
+    history,
+    jobs::{self, JobOptions},
+    load_config,
+    script::Completion,
+    settings::Settings,
+    templates::{find_template, TEMPLATES},
+    ProgrammManipulation,
+};
+use std::path::PathBuf;
+
+const USAGE: &str = "Usage:
+    win-tools-box                                   start the GUI
+    win-tools-box init --template <id> [--output <file>]
+                                                    create a config from a built-in template
+    win-tools-box templates                         list built-in templates
+    win-tools-box <install|uninstall|upgrade|reinstall|repair> <programm>
+                  [--config <file>] [--dry-run] [--keep-artifacts]
+                                                    manipulate a programm from the config
+                                                    (default one from the settings), --dry-run
+                                                    prints the commands without running them,
+                                                    --keep-artifacts leaves working directory
+                                                    of a failed job
+    win-tools-box history [--format <json|csv>]     print recorded jobs, JSON by default";
+
+/// Runs command line mode and returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    match args[0].as_str() {
+        "init" => init(&args[1..]),
+        "install" => manipulate(ProgrammManipulation::Install, &args[1..]),
+        "uninstall" => manipulate(ProgrammManipulation::Uninstall, &args[1..]),
+        "upgrade" => manipulate(ProgrammManipulation::Upgrade, &args[1..]),
+        "reinstall" => manipulate(ProgrammManipulation::Reinstall, &args[1..]),
+        "repair" => manipulate(ProgrammManipulation::Repair, &args[1..]),
+        "history" => print_history(&args[1..]),
+        "templates" => {
+            for t in TEMPLATES {
+                println!("{:<20} {}", t.id, t.description);
+            }
+            0
+        }
+        "-h" | "--help" | "help" => {
+            println!("{}", USAGE);
+            0
+        }
+        other => {
+            eprintln!("Unknown command: {}\n\n{}", other, USAGE);
+            2
+        }
+    }
+}
+
+fn init(args: &[String]) -> i32 {
+    let mut template = None;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--template" | "-t" => template = args.next(),
+            "--output" | "-o" => output = args.next().map(PathBuf::from),
+            other => {
+                eprintln!("Unknown argument: {}\n\n{}", other, USAGE);
+                return 2;
+            }
+        }
+    }
+    let Some(template) = template else {
+        eprintln!("--template is required\n\n{}", USAGE);
+        return 2;
+    };
+    let Some(template) = find_template(template) else {
+        eprintln!(
+            "Unknown template \"{}\", available: {}",
+            template,
+            TEMPLATES
+                .iter()
+                .map(|t| t.id)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        return 2;
+    };
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("{}.json", template.id)));
+    match template.instantiate(&output) {
+        Ok(()) => {
+            println!(
+                "Created {} from \"{}\" template",
+                output.display(),
+                template.title
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+fn print_history(args: &[String]) -> i32 {
+    let mut csv = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next().map(String::as_str)) {
+            ("--format" | "-f", Some("json")) => csv = false,
+            ("--format" | "-f", Some("csv")) => csv = true,
+            _ => {
+                eprintln!("Unknown argument: {}\n\n{}", arg, USAGE);
+                return 2;
+            }
+        }
+    }
+    let entries = match history::load() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let entries: Vec<&history::Entry> = entries.iter().collect();
+    if csv {
+        print!("{}", history::to_csv(&entries));
+    } else {
+        println!("{}", history::to_json(&entries));
+    }
+    0
+}
+
+fn manipulate(manipulation: ProgrammManipulation, args: &[String]) -> i32 {
+    let mut name = None;
+    let mut config = Settings::load().unwrap_or_default().default_config;
+    let mut dry_run = false;
+    let mut options = JobOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" | "-c" => match args.next() {
+                Some(path) => config = PathBuf::from(path),
+                None => {
+                    eprintln!("--config needs a file\n\n{}", USAGE);
+                    return 2;
+                }
+            },
+            "--dry-run" | "-n" => dry_run = true,
+            "--keep-artifacts" => options.keep_artifacts_on_failure = true,
+            other if name.is_none() && !other.starts_with('-') => name = Some(other),
+            other => {
+                eprintln!("Unknown argument: {}\n\n{}", other, USAGE);
+                return 2;
+            }
+        }
+    }
+    let Some(name) = name else {
+        eprintln!("Programm name is required\n\n{}", USAGE);
+        return 2;
+    };
+    let programms = match load_config(&config) {
+        Ok((programms, _)) => programms,
+        Err(e) => {
+            eprintln!("Can't load {}: {}", config.display(), e);
+            return 1;
+        }
+    };
+    let Some(prog) = programms.get(name) else {
+        eprintln!("No programm \"{}\" in {}", name, config.display());
+        return 2;
+    };
+    if let Err(e) = jobs::check(prog, manipulation) {
+        eprintln!("{}", e);
+        return 1;
+    }
+    if dry_run {
+        print!("{}", jobs::dry_run(prog, manipulation));
+        return 0;
+    }
+    let mut downloading = false;
+    let result = jobs::execute(prog, manipulation, options, &mut |downloaded, total| {
+        downloading = true;
+        match total {
+            Some(total) => eprint!("\rDownloading {} / {} KiB", downloaded / 1024, total / 1024),
+            None => eprint!("\rDownloading {} KiB", downloaded / 1024),
+        }
+    });
+    if downloading {
+        eprintln!();
+    }
+    match result {
+        Ok(Completion::Done) => {
+            println!("{} {}: done", manipulation.verb(), name);
+            if let Some(hint) = jobs::env_hint(prog, manipulation) {
+                println!("Environment changed, {}", hint);
+            }
+            0
+        }
+        Ok(Completion::NeedsReboot) => {
+            println!("{} {}: done, reboot to finish", manipulation.verb(), name);
+            0
+        }
+        Err(e) => {
+            eprintln!("{} {}: {}", manipulation.verb(), name, e);
+            1
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use crate::{
+    packages::PackageCommand,
+    paths,
+    script::{Completion, RunPolicy},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Installer downloaded from the internet, verified and then executed
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DownloadSpec {
+    pub url: String,
+    pub sha256: String,
+    /// Tried in order when `url` fails or serves a file with wrong checksum
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<String>,
+    /// Arguments the downloaded installer is run with
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Name of the saved file, last segment of `url` by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    /// Timeout, retries and exit codes of the installer run
+    #[serde(flatten)]
+    pub policy: RunPolicy,
+}
+
+impl DownloadSpec {
+    /// Where `fetch` keeps the verified file, both the checksum and the file name are
+    /// checked not to lead out of the cache
+    pub fn cached_path(&self, cache_dir: &Path) -> Result<PathBuf, String> {
+        let file_name = self.file_name();
+        if !paths::is_file_name(&file_name) {
+            return Err(format!("\"{}\" isn't a file name", file_name));
+        }
+        Ok(cache_dir.join(self.checksum()?).join(file_name))
+    }
+
+    /// Expected sha256 in lowercase hex
+    pub fn checksum(&self) -> Result<String, String> {
+        let checksum = self.sha256.trim().to_lowercase();
+        if checksum.len() != 64 || !checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!(
+                "\"{}\" isn't a sha256 checksum of 64 hex digits",
+                self.sha256
+            ));
+        }
+        Ok(checksum)
+    }
+
+    pub fn file_name(&self) -> String {
+        self.file_name.clone().unwrap_or_else(|| {
+            let path = self.url.split(['?', '#']).next().unwrap_or_default();
+            path.rsplit('/')
+                .next()
+                .filter(|name| !name.is_empty())
+                .unwrap_or("download")
+                .to_string()
+        })
+    }
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Downloads the artifact into `cache_dir` and returns path to the verified file.
+///
+/// Files are cached by checksum, so repeated installs don't hit the network, and
+/// interrupted downloads are resumed from the `.part` file left behind.
+pub fn fetch(
+    spec: &DownloadSpec,
+    cache_dir: &Path,
+    mut progress: impl FnMut(u64, Option<u64>),
+) -> Result<PathBuf, String> {
+    let expected = spec.checksum()?;
+    let path = spec.cached_path(cache_dir)?;
+    let dir = cache_dir.join(&expected);
+    if path.exists() && sha256_file(&path).ok().as_ref() == Some(&expected) {
+        return Ok(path);
+    }
+    fs::create_dir_all(&dir).map_err(|e| format!("Can't create {}: {}", dir.display(), e))?;
+    let part = dir.join(format!("{}.part", spec.file_name()));
+
+    let mut errors = Vec::new();
+    for url in std::iter::once(&spec.url).chain(&spec.mirrors) {
+        if let Err(e) = fetch_from(url, &part, &mut progress) {
+            errors.push(e);
+            continue;
+        }
+        match sha256_file(&part) {
+            Ok(hash) if hash == expected => {
+                fs::rename(&part, &path).map_err(|e| e.to_string())?;
+                return Ok(path);
+            }
+            Ok(hash) => {
+                let _ = fs::remove_file(&part);
+                errors.push(format!("{}: checksum mismatch, got {}", url, hash));
+            }
+            Err(e) => errors.push(format!("{}: {}", url, e)),
+        }
+    }
+    Err(format!("Download failed: {}", errors.join("; ")))
+}
+
+fn fetch_from(
+    url: &str,
+    part: &Path,
+    progress: &mut impl FnMut(u64, Option<u64>),
+) -> Result<(), String> {
+    let existing = part.metadata().map(|m| m.len()).unwrap_or(0);
+    let mut request = ureq::get(url);
+    if existing > 0 {
+        request = request.set("Range", &format!("bytes={}-", existing));
+    }
+    let response = match request.call() {
+        Ok(response) => response,
+        // Part file already holds the whole artifact
+        Err(ureq::Error::Status(416, _)) if existing > 0 => return Ok(()),
+        // ureq errors already mention the url
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let resumed = response.status() == 206;
+    let mut downloaded = if resumed { existing } else { 0 };
+    let total = response
+        .header("Content-Length")
+        .and_then(|len| len.parse::<u64>().ok())
+        .map(|len| len + downloaded);
+    let mut file = if resumed {
+        OpenOptions::new().append(true).open(part)
+    } else {
+        File::create(part)
+    }
+    .map_err(|e| format!("Can't write {}: {}", part.display(), e))?;
+
+    let mut reader = response.into_reader();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = reader
+            .read(&mut buf)
+            .map_err(|e| format!("{}: {}", url, e))?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buf[..read]).map_err(|e| e.to_string())?;
+        downloaded += read as u64;
+        progress(downloaded, total);
+    }
+    Ok(())
+}
+
+/// Command the downloaded installer is run with, `.msi` packages go through msiexec
+pub fn installer_command(
+    path: &Path,
+    args: &[String],
+    elevate: bool,
+) -> Result<PackageCommand, String> {
+    let command = if is_msi(path) {
+        let mut msi_args = vec!["/i".to_string(), path.display().to_string()];
+        msi_args.extend(args.iter().cloned());
+        PackageCommand {
+            program: "msiexec".to_string(),
+            args: msi_args,
+        }
+    } else {
+        PackageCommand {
+            program: path.display().to_string(),
+            args: args.to_vec(),
+        }
+    };
+    if elevate {
+        command.elevated()
+    } else {
+        Ok(command)
+    }
+}
+
+fn is_msi(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("msi"))
+}
+
+/// Runs the downloaded installer
+pub fn run_artifact(
+    spec: &DownloadSpec,
+    path: &Path,
+    elevate: bool,
+    dir: &Path,
+) -> Result<Completion, String> {
+    #[cfg(unix)]
+    if !is_msi(path) {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).map_err(|e| e.to_string())?;
+    }
+    installer_command(path, &spec.args, elevate)?.run(&spec.policy, dir)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::{
+        env,
+        io::{BufRead, BufReader},
+        net::TcpListener,
+        process,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    /// Serves `body` on every request, answering `Range: bytes=N-` with the rest of it.
+    /// Returns the url and the Range headers of requests, `None` for plain ones
+    pub(crate) fn serve(body: Vec<u8>) -> (String, Arc<Mutex<Vec<Option<String>>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/tool-setup.exe", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut range = None;
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("range") {
+                            range = Some(value.trim().to_string());
+                        }
+                    }
+                }
+                let start = range
+                    .as_deref()
+                    .and_then(|r| r.strip_prefix("bytes="))
+                    .and_then(|r| r.trim_end_matches('-').parse::<usize>().ok());
+                let (status, part) = match start {
+                    Some(start) => ("206 Partial Content", &body[start..]),
+                    None => ("200 OK", &body[..]),
+                };
+                seen.lock().unwrap().push(range);
+                let header = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    part.len()
+                );
+                stream.write_all(header.as_bytes()).unwrap();
+                stream.write_all(part).unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    fn body() -> Vec<u8> {
+        (0..200_000).map(|i| (i % 251) as u8).collect()
+    }
+
+    pub(crate) fn sha256(data: &[u8]) -> String {
+        Sha256::digest(data)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    fn spec(url: &str, sha256: &str) -> DownloadSpec {
+        DownloadSpec {
+            url: url.to_string(),
+            sha256: sha256.to_string(),
+            mirrors: Vec::new(),
+            args: Vec::new(),
+            file_name: None,
+            policy: RunPolicy::default(),
+        }
+    }
+
+    fn cache_dir(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("wtb-download-{}-{}", test, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn checksum_mismatch_is_an_error() {
+        let (url, _) = serve(body());
+        let cache = cache_dir("mismatch");
+        let wrong = sha256(b"something else");
+        let error = fetch(&spec(&url, &wrong), &cache, |_, _| {}).unwrap_err();
+        assert!(error.contains("checksum mismatch"), "{}", error);
+        assert!(!cache.join(&wrong).join("tool-setup.exe.part").exists());
+        assert!(!cache.join(&wrong).join("tool-setup.exe").exists());
+    }
+
+    #[test]
+    fn part_file_is_resumed() {
+        let body = body();
+        let (url, requests) = serve(body.clone());
+        let cache = cache_dir("resume");
+        let checksum = sha256(&body);
+        let dir = cache.join(&checksum);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tool-setup.exe.part"), &body[..70_000]).unwrap();
+
+        let path = fetch(&spec(&url, &checksum), &cache, |_, _| {}).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), body);
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![Some("bytes=70000-".to_string())]
+        );
+    }
+
+    #[test]
+    fn cached_file_is_not_downloaded_again() {
+        let body = body();
+        let (url, requests) = serve(body.clone());
+        let cache = cache_dir("cached");
+        let spec = spec(&url, &sha256(&body));
+        let first = fetch(&spec, &cache, |_, _| {}).unwrap();
+        let second = fetch(&spec, &cache, |_, _| {}).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(*requests.lock().unwrap(), vec![None]);
+    }
+
+    #[test]
+    fn checksum_must_be_hex() {
+        let cache = cache_dir("escape");
+        let spec = spec("http://127.0.0.1:1/x", "../../x");
+        assert!(fetch(&spec, &cache, |_, _| {}).is_err());
+        assert!(spec.cached_path(&cache).is_err());
+        assert!(!cache.exists());
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    env,
+    io::{self, IsTerminal},
+    path::Path,
+    process,
+    sync::OnceLock,
+};
+
+/// Overrides the elevation helper, e.g. `sudo -n` or `gsudo`, split on whitespace
+const HELPER_VAR: &str = "WTB_ELEVATE";
+
+/// Whether this process already has administrator (root) rights, checked once
+pub fn is_elevated() -> bool {
+    static ELEVATED: OnceLock<bool> = OnceLock::new();
+    *ELEVATED.get_or_init(|| {
+        let output = if cfg!(windows) {
+            process::Command::new("pwsh")
+                .args([
+                    "-NoProfile",
+                    "-Command",
+                    "([Security.Principal.WindowsPrincipal][Security.Principal.WindowsIdentity]::GetCurrent()).IsInRole('Administrators')",
+                ])
+                .output()
+        } else {
+            process::Command::new("id").arg("-u").output()
+        };
+        output.is_ok_and(|output| {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            matches!(stdout.trim(), "0" | "True")
+        })
+    })
+}
+
+/// Helper elevated commands are prefixed with, `None` when there is none on this system.
+/// Without `WTB_ELEVATE` Windows uses `Start-Process -Verb RunAs` and Linux the first of
+/// sudo, doas or pkexec found in PATH (pkexec first when there is no terminal to ask in)
+pub fn helper() -> Option<Vec<String>> {
+    if let Some(helper) = env::var(HELPER_VAR).ok().filter(|h| !h.trim().is_empty()) {
+        return Some(helper.split_whitespace().map(str::to_string).collect());
+    }
+    if cfg!(windows) {
+        return Some(vec!["runas".to_string()]);
+    }
+    let candidates = if io::stdin().is_terminal() {
+        ["sudo", "doas", "pkexec"]
+    } else {
+        ["pkexec", "sudo", "doas"]
+    };
+    candidates
+        .into_iter()
+        .find(|name| in_path(name))
+        .map(|name| vec![name.to_string()])
+}
+
+fn in_path(name: &str) -> bool {
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths).any(|dir| Path::new(&dir).join(name).is_file())
+    })
+}
+
+/// Short name of the helper for the UI, e.g. "sudo"
+pub fn helper_name() -> Option<String> {
+    helper().and_then(|helper| helper.into_iter().next())
+}
+
+/// Program and arguments running `program` with administrator rights through the helper
+pub fn wrap(program: &str, args: &[String]) -> Result<(String, Vec<String>), String> {
+    let mut helper = helper().ok_or_else(|| {
+        format!(
+            "No sudo, doas or pkexec found, run Win tool box as root or set {}",
+            HELPER_VAR
+        )
+    })?;
+    if cfg!(windows) && helper == ["runas"] {
+        let quote = |s: &str| format!("'{}'", s.replace('\'', "''"));
+        let mut script = format!(
+            "$p = Start-Process -Verb RunAs -Wait -PassThru -FilePath {}",
+            quote(program)
+        );
+        if !args.is_empty() {
+            let args: Vec<String> = args.iter().map(|a| quote(a)).collect();
+            script += &format!(" -ArgumentList {}", args.join(", "));
+        }
+        script += "; exit $p.ExitCode";
+        return Ok((
+            "pwsh".to_string(),
+            vec!["-NoProfile".to_string(), "-Command".to_string(), script],
+        ));
+    }
+    let helper_program = helper.remove(0);
+    helper.push(program.to_string());
+    helper.extend(args.iter().cloned());
+    Ok((helper_program, helper))
+}
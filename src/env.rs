@@ -0,0 +1,688 @@
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::PathBuf,
+    process,
+    sync::{Mutex, MutexGuard},
+};
+
+/// Value of a programm `env` entry, lists are joined with the platform path separator
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum EnvValue {
+    Single(String),
+    List(Vec<String>),
+}
+
+/// Change made to the user environment, recorded to be reverted on uninstall
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum EnvChange {
+    Set {
+        var: String,
+        value: String,
+        /// Value before installation, restored on revert
+        previous: Option<String>,
+        /// User variable before installation, Windows only
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        previous_user: Option<String>,
+    },
+    Append {
+        var: String,
+        /// Entries started processes got, ones they already had stay on revert
+        entries: Vec<String>,
+        /// Entries of the user variable on Windows, of the profile snippet elsewhere
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        user_entries: Vec<String>,
+    },
+    Prepend {
+        var: String,
+        entries: Vec<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        user_entries: Vec<String>,
+    },
+}
+
+const PATH_SEPARATOR: &str = if cfg!(windows) { ";" } else { ":" };
+
+/// Managed variables as processes started by the app see them, `None` for removed ones.
+/// The environment of the app itself is never changed, other threads read it at any time.
+/// Held for the whole apply or revert, so records and user variables are changed by one
+/// job at a time
+static OVERLAY: Mutex<BTreeMap<String, Option<String>>> = Mutex::new(BTreeMap::new());
+
+type Overlay = BTreeMap<String, Option<String>>;
+
+fn overlay() -> MutexGuard<'static, Overlay> {
+    OVERLAY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Value a started process gets
+fn current(overlay: &Overlay, var: &str) -> Option<String> {
+    match overlay.get(var) {
+        Some(value) => value.clone(),
+        None => env::var(var).ok(),
+    }
+}
+
+/// Gives the command variables changed by installed programms, so they can be run right away
+pub fn apply_to(command: &mut process::Command) {
+    for (var, value) in overlay().iter() {
+        match value {
+            Some(value) => command.env(var, value),
+            None => command.env_remove(var),
+        };
+    }
+}
+
+fn records_dir() -> PathBuf {
+    paths::data_dir().join("env")
+}
+
+fn record_path(name: &str) -> PathBuf {
+    records_dir().join(format!("{}.json", name.replace(['/', '\\', ':'], "_")))
+}
+
+/// Shell snippet with all managed variables, sourced from the user profile on Linux
+pub fn profile_snippet_path() -> PathBuf {
+    paths::config_dir().join("env.sh")
+}
+
+/// Reminder to source `env.sh`, `None` on Windows and when a shell profile already does it
+pub fn source_hint() -> Option<String> {
+    if cfg!(windows) {
+        return None;
+    }
+    let home = PathBuf::from(env::var_os("HOME")?);
+    let profiles = [
+        ".profile",
+        ".bash_profile",
+        ".bashrc",
+        ".zshrc",
+        ".zprofile",
+        ".config/fish/config.fish",
+    ];
+    let sourced = profiles.iter().any(|profile| {
+        fs::read_to_string(home.join(profile)).is_ok_and(|text| text.contains("wtb/env.sh"))
+    });
+    (!sourced).then(|| {
+        format!(
+            "add \". {}\" to your shell profile to get the variables in new terminals",
+            profile_snippet_path().display()
+        )
+    })
+}
+
+/// Replaces `{variable}` placeholders, one without a value is an error. Only lowercase
+/// names count, so script blocks like `{ ... }` and `${HOME}` are left alone
+pub fn expand(template: &str, variables: &[(&str, String)]) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let name = rest
+            .find('}')
+            .map(|end| &rest[..end])
+            .filter(|name| is_placeholder(name) && !expanded.ends_with('$'));
+        let Some(name) = name else {
+            expanded.push('{');
+            continue;
+        };
+        let (_, value) = variables
+            .iter()
+            .find(|(variable, _)| *variable == name)
+            .ok_or_else(|| {
+                let known: Vec<_> = variables
+                    .iter()
+                    .map(|(v, _)| format!("{{{}}}", v))
+                    .collect();
+                format!(
+                    "{{{}}} has no value in \"{}\", available: {}",
+                    name,
+                    template,
+                    known.join(", ")
+                )
+            })?;
+        expanded.push_str(value);
+        rest = &rest[name.len() + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn is_placeholder(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Changes the programm environment consists of, with placeholders expanded
+fn planned_changes(
+    vars: &BTreeMap<String, EnvValue>,
+    variables: &[(&str, String)],
+) -> Result<Vec<EnvChange>, String> {
+    vars.iter()
+        .map(|(key, value)| {
+            let values = match value {
+                EnvValue::Single(v) => vec![expand(v, variables)?],
+                EnvValue::List(list) => list
+                    .iter()
+                    .map(|v| expand(v, variables))
+                    .collect::<Result<_, _>>()?,
+            };
+            Ok(if let Some(var) = key.strip_suffix("_append") {
+                EnvChange::Append {
+                    var: var.to_string(),
+                    entries: values,
+                    user_entries: Vec::new(),
+                }
+            } else if let Some(var) = key.strip_suffix("_prepend") {
+                EnvChange::Prepend {
+                    var: var.to_string(),
+                    entries: values,
+                    user_entries: Vec::new(),
+                }
+            } else {
+                EnvChange::Set {
+                    var: key.clone(),
+                    value: values.join(PATH_SEPARATOR),
+                    previous: None,
+                    previous_user: None,
+                }
+            })
+        })
+        .collect()
+}
+
+/// What `apply` would do, one line per change
+pub fn describe(
+    vars: &BTreeMap<String, EnvValue>,
+    variables: &[(&str, String)],
+) -> Result<Vec<String>, String> {
+    Ok(planned_changes(vars, variables)?
+        .iter()
+        .map(|change| match change {
+            EnvChange::Set { var, value, .. } => format!("set {} to {}", var, value),
+            EnvChange::Append { var, entries, .. } => {
+                format!("append {} to {}", entries.join(PATH_SEPARATOR), var)
+            }
+            EnvChange::Prepend { var, entries, .. } => {
+                format!("prepend {} to {}", entries.join(PATH_SEPARATOR), var)
+            }
+        })
+        .collect())
+}
+
+/// Applies programm environment in place of its previous record, which stays in effect
+/// when the new one can't be applied
+pub fn apply(
+    name: &str,
+    vars: &BTreeMap<String, EnvValue>,
+    variables: &[(&str, String)],
+) -> Result<(), String> {
+    let planned = planned_changes(vars, variables)?;
+    let mut overlay = overlay();
+    let old = read_record(name)?;
+    revert_all(&mut overlay, &old)?;
+    let changes = match apply_all(&mut overlay, planned) {
+        Ok(changes) => changes,
+        Err(e) => {
+            // Old record is still on disk, its changes are simply made again
+            let old = old.into_iter().map(EnvChange::planned).collect();
+            return Err(match apply_all(&mut overlay, old) {
+                Ok(_) => e,
+                Err(restore) => format!("{}; can't restore previous environment: {}", e, restore),
+            });
+        }
+    };
+
+    fs::create_dir_all(records_dir()).map_err(|e| e.to_string())?;
+    let record = serde_json::to_vec_pretty(&changes).map_err(|e| e.to_string())?;
+    fs::write(record_path(name), record).map_err(|e| e.to_string())?;
+    write_profile_snippet()
+}
+
+/// Undoes everything recorded for the programm, nothing happens without a record
+pub fn revert(name: &str) -> Result<(), String> {
+    let mut overlay = overlay();
+    let path = record_path(name);
+    if !path.exists() {
+        return Ok(());
+    }
+    revert_all(&mut overlay, &read_record(name)?)?;
+    fs::remove_file(&path).map_err(|e| e.to_string())?;
+    write_profile_snippet()
+}
+
+/// Changes recorded for the programm, none without a record
+fn read_record(name: &str) -> Result<Vec<EnvChange>, String> {
+    match fs::read(record_path(name)) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| e.to_string()),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+/// Applies the changes in order, partially applied ones are undone on failure
+fn apply_all(overlay: &mut Overlay, planned: Vec<EnvChange>) -> Result<Vec<EnvChange>, String> {
+    let mut changes: Vec<EnvChange> = Vec::new();
+    for change in planned {
+        match apply_change(overlay, change) {
+            Ok(change) => changes.push(change),
+            Err(e) => {
+                for change in changes.iter().rev() {
+                    let _ = revert_change(overlay, change);
+                }
+                return Err(match changes.len() {
+                    0 => e,
+                    count => format!("{}, reverted {} environment changes", e, count),
+                });
+            }
+        }
+    }
+    Ok(changes)
+}
+
+fn revert_all(overlay: &mut Overlay, changes: &[EnvChange]) -> Result<(), String> {
+    changes
+        .iter()
+        .rev()
+        .try_for_each(|change| revert_change(overlay, change))
+}
+
+fn apply_change(overlay: &mut Overlay, change: EnvChange) -> Result<EnvChange, String> {
+    let current = current(overlay, change.var());
+    let (new_value, change) = match change {
+        EnvChange::Set { var, value, .. } => {
+            let previous_user = if cfg!(windows) {
+                get_user_var(&var)?
+            } else {
+                None
+            };
+            (
+                value.clone(),
+                EnvChange::Set {
+                    var,
+                    value,
+                    previous: current,
+                    previous_user,
+                },
+            )
+        }
+        EnvChange::Append { var, entries, .. } => {
+            let mut list = split_list(current.as_deref());
+            let added = missing_entries(&list, &entries);
+            list.extend(added.iter().cloned());
+            let user_entries = add_user_entries(&var, &entries, true)?;
+            (
+                list.join(PATH_SEPARATOR),
+                EnvChange::Append {
+                    var,
+                    entries: added,
+                    user_entries,
+                },
+            )
+        }
+        EnvChange::Prepend { var, entries, .. } => {
+            let list = split_list(current.as_deref());
+            let mut added = missing_entries(&list, &entries);
+            let user_entries = add_user_entries(&var, &entries, false)?;
+            let change = EnvChange::Prepend {
+                var,
+                entries: added.clone(),
+                user_entries,
+            };
+            added.extend(list);
+            (added.join(PATH_SEPARATOR), change)
+        }
+    };
+
+    if cfg!(windows) {
+        if let EnvChange::Set { var, value, .. } = &change {
+            set_user_var(var, Some(value))?;
+        }
+    }
+    overlay.insert(change.var().to_string(), Some(new_value));
+    Ok(change)
+}
+
+fn revert_change(overlay: &mut Overlay, change: &EnvChange) -> Result<(), String> {
+    match change {
+        EnvChange::Set {
+            var,
+            previous,
+            previous_user,
+            ..
+        } => {
+            if cfg!(windows) {
+                set_user_var(var, previous_user.as_deref())?;
+            }
+            overlay.insert(var.clone(), previous.clone());
+        }
+        EnvChange::Append {
+            var,
+            entries,
+            user_entries,
+        }
+        | EnvChange::Prepend {
+            var,
+            entries,
+            user_entries,
+        } => {
+            if cfg!(windows) && !user_entries.is_empty() {
+                let mut list = split_list(get_user_var(var)?.as_deref());
+                list.retain(|e| !user_entries.contains(e));
+                set_user_var(var, Some(&list.join(PATH_SEPARATOR)))?;
+            }
+            let mut list = split_list(current(overlay, var).as_deref());
+            list.retain(|e| !entries.contains(e));
+            overlay.insert(var.clone(), Some(list.join(PATH_SEPARATOR)));
+        }
+    }
+    Ok(())
+}
+
+impl EnvChange {
+    /// Recorded change as it was planned, so it can be applied again
+    fn planned(self) -> EnvChange {
+        match self {
+            EnvChange::Set { var, value, .. } => EnvChange::Set {
+                var,
+                value,
+                previous: None,
+                previous_user: None,
+            },
+            // Entries the user variable had without the app are added only to the overlay
+            EnvChange::Append {
+                var,
+                entries,
+                user_entries,
+            } => EnvChange::Append {
+                var,
+                entries: merge_entries(entries, user_entries),
+                user_entries: Vec::new(),
+            },
+            EnvChange::Prepend {
+                var,
+                entries,
+                user_entries,
+            } => EnvChange::Prepend {
+                var,
+                entries: merge_entries(entries, user_entries),
+                user_entries: Vec::new(),
+            },
+        }
+    }
+
+    fn var(&self) -> &str {
+        match self {
+            EnvChange::Set { var, .. }
+            | EnvChange::Append { var, .. }
+            | EnvChange::Prepend { var, .. } => var,
+        }
+    }
+}
+
+/// Entries the list doesn't have yet, each once
+fn missing_entries(list: &[String], entries: &[String]) -> Vec<String> {
+    let mut missing: Vec<String> = Vec::new();
+    for entry in entries {
+        if !list.contains(entry) && !missing.contains(entry) {
+            missing.push(entry.clone());
+        }
+    }
+    missing
+}
+
+fn merge_entries(mut entries: Vec<String>, more: Vec<String>) -> Vec<String> {
+    let missing = missing_entries(&entries, &more);
+    entries.extend(missing);
+    entries
+}
+
+/// Adds entries missing from the user variable on Windows and returns them. Elsewhere the
+/// profile snippet gets all of them, the shell profile is the user environment there
+fn add_user_entries(var: &str, entries: &[String], append: bool) -> Result<Vec<String>, String> {
+    if !cfg!(windows) {
+        return Ok(entries.to_vec());
+    }
+    let mut list = split_list(get_user_var(var)?.as_deref());
+    let added = missing_entries(&list, entries);
+    if added.is_empty() {
+        return Ok(added);
+    }
+    if append {
+        list.extend(added.iter().cloned());
+    } else {
+        list.splice(0..0, added.iter().cloned());
+    }
+    set_user_var(var, Some(&list.join(PATH_SEPARATOR)))?;
+    Ok(added)
+}
+
+fn split_list(value: Option<&str>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split(PATH_SEPARATOR)
+        .filter(|e| !e.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Regenerates `env.sh` from all records, nothing to do on Windows where registry is used
+fn write_profile_snippet() -> Result<(), String> {
+    if cfg!(windows) {
+        return Ok(());
+    }
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "'\\''"));
+    let mut snippet = String::from("# Managed by Win tool box, changes will be overwritten\n");
+    let mut records = fs::read_dir(records_dir())
+        .map(|dir| {
+            dir.filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    records.sort();
+    for record in records {
+        let Some(changes) = fs::read(&record)
+            .ok()
+            .and_then(|data| serde_json::from_slice::<Vec<EnvChange>>(&data).ok())
+        else {
+            continue;
+        };
+        for change in changes {
+            let line = match &change {
+                EnvChange::Set { var, value, .. } => format!("export {}={}", var, quote(value)),
+                EnvChange::Append { user_entries, .. }
+                | EnvChange::Prepend { user_entries, .. }
+                    if user_entries.is_empty() =>
+                {
+                    continue;
+                }
+                EnvChange::Append {
+                    var, user_entries, ..
+                } => format!(
+                    "export {var}=\"${{{var}:+${var}:}}\"{}",
+                    quote(&user_entries.join(PATH_SEPARATOR))
+                ),
+                EnvChange::Prepend {
+                    var, user_entries, ..
+                } => format!(
+                    "export {var}={}\"${{{var}:+:${var}}}\"",
+                    quote(&user_entries.join(PATH_SEPARATOR))
+                ),
+            };
+            snippet.push_str(&line);
+            snippet.push('\n');
+        }
+    }
+    let path = profile_snippet_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(&path, snippet).map_err(|e| format!("Can't write {}: {}", path.display(), e))
+}
+
+fn ps_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Registry key of the user variables
+const USER_ENVIRONMENT: &str = "HKCU:\\Environment";
+
+/// Raw value of the user variable, references like `%USERPROFILE%` are kept unexpanded
+fn get_user_var(var: &str) -> Result<Option<String>, String> {
+    let output = process::Command::new("pwsh")
+        .args([
+            "-NoProfile",
+            "-Command",
+            &format!(
+                "(Get-Item -LiteralPath '{}').GetValue({}, $null, 'DoNotExpandEnvironmentNames')",
+                USER_ENVIRONMENT,
+                ps_quote(var)
+            ),
+        ])
+        .output()
+        .map_err(|e| format!("Can't read {}: {}", var, e))?;
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(Some(value).filter(|v| !v.is_empty()))
+}
+
+/// Writes the user variable to the registry and tells running programs about it. PATH and
+/// values with `%` references are REG_EXPAND_SZ, `SetEnvironmentVariable` would turn
+/// them into REG_SZ and break the references
+fn set_user_var(var: &str, value: Option<&str>) -> Result<(), String> {
+    let write = match value {
+        Some(value) => {
+            let kind = if var.eq_ignore_ascii_case("path") || value.contains('%') {
+                "ExpandString"
+            } else {
+                "String"
+            };
+            format!(
+                "New-ItemProperty -LiteralPath '{}' -Name {} -Value {} -PropertyType {} -Force | Out-Null",
+                USER_ENVIRONMENT,
+                ps_quote(var),
+                ps_quote(value),
+                kind
+            )
+        }
+        None => format!(
+            "Remove-ItemProperty -LiteralPath '{}' -Name {} -ErrorAction SilentlyContinue",
+            USER_ENVIRONMENT,
+            ps_quote(var)
+        ),
+    };
+    // WM_SETTINGCHANGE with "Environment", as `SetEnvironmentVariable` sends it
+    let broadcast = "Add-Type -Namespace Wtb -Name Native -MemberDefinition '[DllImport(\"user32.dll\", CharSet = CharSet.Unicode)] public static extern System.IntPtr SendMessageTimeout(System.IntPtr hWnd, uint msg, System.UIntPtr wParam, string lParam, uint flags, uint timeout, out System.UIntPtr result);'; $r = [UIntPtr]::Zero; [Wtb.Native]::SendMessageTimeout([IntPtr]0xffff, 0x1A, [UIntPtr]::Zero, 'Environment', 2, 5000, [ref]$r) | Out-Null";
+    let status = process::Command::new("pwsh")
+        .args([
+            "-NoProfile",
+            "-Command",
+            &format!("$ErrorActionPreference = 'Stop'; {}; {}", write, broadcast),
+        ])
+        .status()
+        .map_err(|e| format!("Can't set {}: {}", var, e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("Can't set {}: {}", var, status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_are_expanded() {
+        let variables = [("install_dir", "/opt/zig".to_string())];
+        assert_eq!(
+            expand("{install_dir}/bin", &variables).unwrap(),
+            "/opt/zig/bin"
+        );
+        // Script blocks and shell variables aren't placeholders
+        assert_eq!(
+            expand("if ($x) { echo ${HOME} }", &variables).unwrap(),
+            "if ($x) { echo ${HOME} }"
+        );
+    }
+
+    #[cfg(not(windows))]
+    fn list_change(append: bool, entries: &[&str]) -> EnvChange {
+        let var = "WTB_TEST_PATH".to_string();
+        let entries = entries.iter().map(|e| e.to_string()).collect();
+        let user_entries = Vec::new();
+        if append {
+            EnvChange::Append {
+                var,
+                entries,
+                user_entries,
+            }
+        } else {
+            EnvChange::Prepend {
+                var,
+                entries,
+                user_entries,
+            }
+        }
+    }
+
+    #[cfg(not(windows))]
+    fn overlay_with(value: &str) -> Overlay {
+        Overlay::from([("WTB_TEST_PATH".to_string(), Some(value.to_string()))])
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn present_entries_stay_on_revert() {
+        for append in [true, false] {
+            let mut overlay = overlay_with("/usr/bin:/opt/tool/bin");
+            let change = list_change(append, &["/opt/tool/bin", "/opt/new/bin", "/opt/new/bin"]);
+            let change = apply_change(&mut overlay, change).unwrap();
+            let expected = if append {
+                "/usr/bin:/opt/tool/bin:/opt/new/bin"
+            } else {
+                "/opt/new/bin:/usr/bin:/opt/tool/bin"
+            };
+            assert_eq!(current(&overlay, "WTB_TEST_PATH").unwrap(), expected);
+
+            revert_change(&mut overlay, &change).unwrap();
+            assert_eq!(
+                current(&overlay, "WTB_TEST_PATH").unwrap(),
+                "/usr/bin:/opt/tool/bin"
+            );
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn entries_already_present_are_not_recorded() {
+        let mut overlay = overlay_with("/opt/tool/bin");
+        let change = apply_change(&mut overlay, list_change(true, &["/opt/tool/bin"])).unwrap();
+        let EnvChange::Append {
+            entries,
+            user_entries,
+            ..
+        } = &change
+        else {
+            panic!("{:?}", change);
+        };
+        assert!(entries.is_empty());
+        // Profile snippet still gets the entry, the next login shell wouldn't have it otherwise
+        assert_eq!(user_entries, &["/opt/tool/bin"]);
+
+        revert_change(&mut overlay, &change).unwrap();
+        assert_eq!(current(&overlay, "WTB_TEST_PATH").unwrap(), "/opt/tool/bin");
+    }
+
+    #[test]
+    fn unknown_placeholder_is_an_error() {
+        let error = expand("{install_dir}/bin", &[("bin_dir", "/b".to_string())]).unwrap_err();
+        assert!(error.contains("{install_dir}"), "{}", error);
+    }
+}
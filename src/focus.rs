@@ -0,0 +1,76 @@
+use iced::{
+    event,
+    keyboard::{self, key::Named, Key, Modifiers},
+    Event,
+};
+
+/// Part of the programms menu arrow keys move in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Pane {
+    #[default]
+    List,
+    Actions,
+}
+
+impl Pane {
+    const ORDER: [Pane; 2] = [Pane::List, Pane::Actions];
+
+    pub fn next(self) -> Pane {
+        let i = Self::ORDER.iter().position(|p| *p == self).unwrap_or(0);
+        Self::ORDER[(i + 1) % Self::ORDER.len()]
+    }
+
+    pub fn previous(self) -> Pane {
+        let i = Self::ORDER.iter().position(|p| *p == self).unwrap_or(0);
+        Self::ORDER[(i + Self::ORDER.len() - 1) % Self::ORDER.len()]
+    }
+}
+
+/// Keyboard selection inside the current menu, reset when the menu changes
+#[derive(Debug, Default)]
+pub struct Focus {
+    pub pane: Pane,
+    /// Highlighted row of the programms, updates or templates list
+    pub row: usize,
+    /// Highlighted programm action button
+    pub action: usize,
+}
+
+impl Focus {
+    /// Moves the row by `delta` inside a list of `len` rows
+    pub fn move_row(&mut self, delta: isize, len: usize) {
+        self.row = self
+            .row
+            .saturating_add_signed(delta)
+            .min(len.saturating_sub(1));
+    }
+
+    pub fn move_action(&mut self, delta: isize, len: usize) {
+        self.action = self
+            .action
+            .saturating_add_signed(delta)
+            .min(len.saturating_sub(1));
+    }
+}
+
+/// Text the key types, shortcuts with Ctrl, Alt or Logo type nothing
+pub fn typed_text(key: &Key, modifiers: Modifiers) -> Option<String> {
+    if modifiers.control() || modifiers.alt() || modifiers.logo() {
+        return None;
+    }
+    match key {
+        Key::Character(ch) => Some(ch.to_string()),
+        Key::Named(Named::Space) => Some(" ".to_string()),
+        _ => None,
+    }
+}
+
+/// Keyboard events not taken by a focused widget, like a text input
+pub fn free_keyboard_event(event: Event, status: event::Status) -> Option<Event> {
+    match (&event, status) {
+        (Event::Keyboard(keyboard::Event::KeyPressed { .. }), event::Status::Ignored) => {
+            Some(event)
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{Programm, ProgrammStatus};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+/// Section of programms without a category
+pub const OTHER: &str = "Other";
+
+/// Order of programms inside a section of the list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Name,
+    /// Programms needing attention first: failed, broken, outdated
+    Status,
+    /// Last installed, upgraded or run first, by the history
+    RecentlyUsed,
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 3] = [SortOrder::Name, SortOrder::Status, SortOrder::RecentlyUsed];
+}
+
+impl fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SortOrder::Name => "by name",
+            SortOrder::Status => "by status",
+            SortOrder::RecentlyUsed => "recently used",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Grouping {
+    #[default]
+    Category,
+    /// One flat list without section headers
+    None,
+}
+
+impl Grouping {
+    pub const ALL: [Grouping; 2] = [Grouping::Category, Grouping::None];
+}
+
+impl fmt::Display for Grouping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Grouping::Category => "in categories",
+            Grouping::None => "in one list",
+        })
+    }
+}
+
+/// Section of the programms list
+pub struct Group<'a> {
+    /// Category, empty without grouping
+    pub name: &'a str,
+    pub installed: usize,
+    pub programms: Vec<&'a Programm>,
+    pub collapsed: bool,
+}
+
+/// How the programms list is shown
+pub struct ListView<'a> {
+    pub grouping: Grouping,
+    pub sort: SortOrder,
+    pub collapsed: &'a BTreeSet<String>,
+    /// Programm name to the unix time it was last used
+    pub last_used: &'a BTreeMap<String, u64>,
+}
+
+impl ListView<'_> {
+    /// Sections in alphabetical order with "Other" last, collapsed ones keep their programms
+    /// for the counts
+    pub fn groups<'p>(&self, programms: &'p BTreeMap<String, Programm>) -> Vec<Group<'p>> {
+        let mut sections: BTreeMap<(bool, &str), Vec<&Programm>> = BTreeMap::new();
+        for prog in programms.values() {
+            let name = match self.grouping {
+                Grouping::Category => prog.category(),
+                Grouping::None => "",
+            };
+            sections
+                .entry((name == OTHER, name))
+                .or_default()
+                .push(prog);
+        }
+        sections
+            .into_iter()
+            .map(|((_, name), mut programms)| {
+                self.sort_programms(&mut programms);
+                Group {
+                    name,
+                    installed: programms.iter().filter(|p| p.status.is_installed()).count(),
+                    collapsed: !name.is_empty() && self.collapsed.contains(name),
+                    programms,
+                }
+            })
+            .collect()
+    }
+
+    /// Names of programms in expanded sections, in the order they are shown. Keyboard
+    /// focus moves through them
+    pub fn visible<'p>(&self, programms: &'p BTreeMap<String, Programm>) -> Vec<&'p str> {
+        self.groups(programms)
+            .into_iter()
+            .filter(|group| !group.collapsed)
+            .flat_map(|group| group.programms)
+            .map(|prog| prog.name.as_str())
+            .collect()
+    }
+
+    /// Stable sorts, so programms equal by the order stay by name
+    fn sort_programms(&self, programms: &mut [&Programm]) {
+        match self.sort {
+            SortOrder::Name => {}
+            SortOrder::Status => programms.sort_by_key(|p| attention(p.status)),
+            SortOrder::RecentlyUsed => {
+                programms.sort_by_key(|p| Reverse(self.last_used.get(&p.name).copied()))
+            }
+        }
+    }
+}
+
+/// Lower comes first when sorting by status
+fn attention(status: ProgrammStatus) -> u8 {
+    match status {
+        ProgrammStatus::Failed => 0,
+        ProgrammStatus::Broken => 1,
+        ProgrammStatus::Outdated => 2,
+        ProgrammStatus::NeedsReboot => 3,
+        ProgrammStatus::Installing | ProgrammStatus::Uninstalling => 4,
+        ProgrammStatus::Detecting => 5,
+        ProgrammStatus::Installed => 6,
+        ProgrammStatus::NotInstalled => 7,
+        ProgrammStatus::Unknown => 8,
+        ProgrammStatus::Unsupported => 9,
+    }
+}
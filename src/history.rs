@@ -0,0 +1,335 @@
+use crate::{paths, script::RunRecord};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    process,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Finished job or programm run, appended to `history.jsonl` and never changed afterwards
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Entry {
+    /// Unix time the job started, seconds
+    pub started: u64,
+    pub user: String,
+    pub host: String,
+    pub programm: String,
+    /// install, uninstall, upgrade, reinstall, repair or run
+    pub action: String,
+    /// What the job did with placeholders resolved, as shown by dry run
+    pub steps: Vec<String>,
+    /// Exit code of the last process the job ran, `None` when nothing ran or it was killed
+    pub exit_code: Option<i32>,
+    pub result: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    /// Commands the job ran with their output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Ok,
+    NeedsReboot,
+    Failed,
+    Broken,
+}
+
+impl Outcome {
+    pub const ALL: [Outcome; 4] = [
+        Outcome::Ok,
+        Outcome::NeedsReboot,
+        Outcome::Failed,
+        Outcome::Broken,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::NeedsReboot => "needs reboot",
+            Outcome::Failed => "failed",
+            Outcome::Broken => "broken",
+        }
+    }
+}
+
+pub fn history_path() -> PathBuf {
+    paths::data_dir().join("history.jsonl")
+}
+
+fn logs_dir() -> PathBuf {
+    paths::data_dir().join("logs")
+}
+
+/// File next to the history an export goes to, named after the time of the export
+pub fn export_path(extension: &str) -> PathBuf {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let time = format_time(now.as_secs());
+    let stamp = time.trim_end_matches(" UTC").replace([' ', ':'], "-");
+    paths::data_dir().join(format!("history-{}.{}", stamp, extension))
+}
+
+impl Entry {
+    /// Entry of a job starting now, the result is filled by `finish`
+    pub fn begin(programm: &str, action: &str) -> Entry {
+        Entry {
+            started: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            user: current_user(),
+            host: current_host(),
+            programm: programm.to_string(),
+            action: action.to_string(),
+            steps: Vec::new(),
+            exit_code: None,
+            result: Outcome::Ok,
+            error: None,
+            duration_ms: 0,
+            log_file: None,
+        }
+    }
+
+    pub fn finish(
+        &mut self,
+        duration: Duration,
+        result: Outcome,
+        error: Option<String>,
+        runs: &[RunRecord],
+    ) {
+        self.duration_ms = duration.as_millis() as u64;
+        self.result = result;
+        self.error = error;
+        self.exit_code = runs.last().and_then(|run| run.exit_code);
+    }
+}
+
+/// Writes the log of the job runs and appends the entry to the history
+pub fn record(mut entry: Entry, runs: &[RunRecord]) -> Result<(), String> {
+    if !runs.is_empty() {
+        entry.log_file = Some(write_log(&entry, runs)?);
+    }
+    let path = history_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let mut line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| format!("Can't write {}: {}", path.display(), e))
+}
+
+fn write_log(entry: &Entry, runs: &[RunRecord]) -> Result<PathBuf, String> {
+    let dir = logs_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    // Milliseconds keep apart jobs of the same programm started within a second
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_millis();
+    let path = dir.join(format!(
+        "{}{:03}-{}-{}.log",
+        entry.started,
+        millis,
+        entry.action,
+        entry.programm.replace(['/', '\\', ':'], "_")
+    ));
+    let mut log = format!(
+        "{} {} by {}@{} at {}\n",
+        entry.action,
+        entry.programm,
+        entry.user,
+        entry.host,
+        format_time(entry.started)
+    );
+    for run in runs {
+        log += &format!("\n$ {}\n", run.command);
+        log += &match (&run.error, run.exit_code) {
+            (Some(error), _) => format!("{}\n", error),
+            (None, Some(code)) => format!("exit code {} after {} ms\n", code, run.duration_ms),
+            (None, None) => format!("killed by a signal after {} ms\n", run.duration_ms),
+        };
+        for (name, output) in [("stdout", &run.stdout), ("stderr", &run.stderr)] {
+            if !output.trim().is_empty() {
+                log += &format!("--- {}\n{}\n", name, output.trim_end());
+            }
+        }
+    }
+    fs::write(&path, log).map_err(|e| format!("Can't write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// All recorded entries, oldest first, damaged lines are skipped
+pub fn load() -> Result<Vec<Entry>, String> {
+    let path = history_path();
+    let data = match fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Can't read {}: {}", path.display(), e)),
+    };
+    Ok(data
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Deletes job logs older than `days` and returns how many were deleted, history
+/// entries stay and point to missing logs then
+pub fn prune_logs(days: u32) -> Result<usize, String> {
+    let dir = logs_dir();
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(format!("Can't read {}: {}", dir.display(), e)),
+    };
+    let max_age = Duration::from_secs(u64::from(days) * 86400);
+    let mut deleted = 0;
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let old = entry
+            .metadata()
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > max_age);
+        if old && fs::remove_file(entry.path()).is_ok() {
+            deleted += 1;
+        }
+    }
+    Ok(deleted)
+}
+
+pub fn to_json(entries: &[&Entry]) -> String {
+    serde_json::to_string_pretty(entries).unwrap_or_default()
+}
+
+pub fn to_csv(entries: &[&Entry]) -> String {
+    let quote = |s: &str| {
+        if s.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_string()
+        }
+    };
+    let mut csv = String::from(
+        "started,user,host,programm,action,result,exit_code,duration_ms,error,log_file,steps\n",
+    );
+    for entry in entries {
+        let fields = [
+            format_time(entry.started),
+            entry.user.clone(),
+            entry.host.clone(),
+            entry.programm.clone(),
+            entry.action.clone(),
+            entry.result.label().to_string(),
+            entry.exit_code.map(|c| c.to_string()).unwrap_or_default(),
+            entry.duration_ms.to_string(),
+            entry.error.clone().unwrap_or_default(),
+            entry
+                .log_file
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            entry.steps.join("; "),
+        ];
+        let line: Vec<String> = fields.iter().map(|f| quote(f)).collect();
+        csv += &line.join(",");
+        csv.push('\n');
+    }
+    csv
+}
+
+/// `YYYY-MM-DD HH:MM:SS UTC`
+pub fn format_time(unix_secs: u64) -> String {
+    let days = (unix_secs / 86400) as i64;
+    let secs = unix_secs % 86400;
+    // Civil date from days since 1970-01-01, Howard Hinnant's algorithm
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+fn current_user() -> String {
+    env::var("USERNAME")
+        .or_else(|_| env::var("USER"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn current_host() -> String {
+    if let Some(host) = env::var("COMPUTERNAME")
+        .or_else(|_| env::var("HOSTNAME"))
+        .ok()
+        .filter(|h| !h.is_empty())
+    {
+        return host;
+    }
+    process::Command::new("hostname")
+        .output()
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(error: &str) -> Entry {
+        let mut entry = Entry::begin("git", "install");
+        entry.started = 0;
+        entry.user = "me".to_string();
+        entry.host = "box".to_string();
+        entry.error = Some(error.to_string());
+        entry
+    }
+
+    #[test]
+    fn times_are_formatted() {
+        assert_eq!(format_time(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_time(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_time(1_700_000_000), "2023-11-14 22:13:20 UTC");
+        assert_eq!(format_time(4_107_542_399), "2100-02-28 23:59:59 UTC");
+    }
+
+    #[test]
+    fn csv_fields_are_quoted() {
+        let entries = [entry("exit code 1, see \"log\""), entry("plain")];
+        let csv = to_csv(&entries.iter().collect::<Vec<_>>());
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[1],
+            "1970-01-01 00:00:00 UTC,me,box,git,install,ok,,0,\"exit code 1, see \"\"log\"\"\",,"
+        );
+        assert_eq!(
+            lines[2],
+            "1970-01-01 00:00:00 UTC,me,box,git,install,ok,,0,plain,,"
+        );
+    }
+}
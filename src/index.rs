@@ -0,0 +1,259 @@
+use crate::Programm;
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use std::{cmp::Reverse, collections::BTreeMap, ops::Range};
+
+/// Characters of description shown around the first match
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Inverted index of programm descriptions, built from their markdown on config load
+#[derive(Debug, Default)]
+pub struct DocsIndex {
+    /// Description of every programm as plain text
+    texts: BTreeMap<String, String>,
+    /// Token, then programm name and byte ranges of the token in its text
+    postings: BTreeMap<String, BTreeMap<String, Vec<Range<usize>>>>,
+}
+
+/// Programm name to score and matched ranges
+type Matches<'a> = BTreeMap<&'a str, (usize, Vec<Range<usize>>)>;
+
+/// Programm whose description has all query words
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocHit {
+    pub programm: String,
+    /// Occurrences of query words, whole words count twice
+    pub score: usize,
+    pub snippet: Snippet,
+}
+
+/// Piece of description around a match
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snippet {
+    pub text: String,
+    /// Byte ranges of matched words in `text`
+    pub highlights: Vec<Range<usize>>,
+}
+
+impl DocsIndex {
+    pub fn build(programms: &BTreeMap<String, Programm>) -> Self {
+        let mut index = DocsIndex::default();
+        for prog in programms.values() {
+            let text = plain_text(&prog.description_md);
+            for (range, token) in tokenize(&text) {
+                index
+                    .postings
+                    .entry(token)
+                    .or_default()
+                    .entry(prog.name.clone())
+                    .or_default()
+                    .push(range);
+            }
+            index.texts.insert(prog.name.clone(), text);
+        }
+        index
+    }
+
+    /// Programms with every query word in the description, best first. Words match as
+    /// prefixes too, so "компилят" finds "компилятора" without stemming
+    pub fn search(&self, query: &str) -> Vec<DocHit> {
+        let words: Vec<String> = tokenize(query).map(|(_, token)| token).collect();
+        if words.is_empty() {
+            return Vec::new();
+        }
+        // Narrowed by every word
+        let mut found: Option<Matches> = None;
+        for word in &words {
+            let mut matches = Matches::new();
+            for (token, programms) in self
+                .postings
+                .range(word.clone()..)
+                .take_while(|(token, _)| token.starts_with(word.as_str()))
+            {
+                let weight = if token == word { 2 } else { 1 };
+                for (name, ranges) in programms {
+                    let entry = matches.entry(name.as_str()).or_default();
+                    entry.0 += weight * ranges.len();
+                    entry.1.extend(ranges.iter().cloned());
+                }
+            }
+            found = Some(match found {
+                None => matches,
+                Some(previous) => previous
+                    .into_iter()
+                    .filter_map(|(name, (score, mut ranges))| {
+                        let (more, other) = matches.remove(name)?;
+                        ranges.extend(other);
+                        Some((name, (score + more, ranges)))
+                    })
+                    .collect(),
+            });
+        }
+        let mut hits: Vec<DocHit> = found
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, (score, mut ranges))| {
+                ranges.sort_by_key(|range| range.start);
+                ranges.dedup();
+                DocHit {
+                    programm: name.to_string(),
+                    score,
+                    snippet: snippet(&self.texts[name], &ranges),
+                }
+            })
+            .collect();
+        hits.sort_by_key(|hit| Reverse(hit.score));
+        hits
+    }
+}
+
+/// Words of the text with their byte ranges, lowercase. Letters and digits of any
+/// script make words, so Latin and Cyrillic text split the same way, "ё" is searched as "е"
+pub fn tokenize(text: &str) -> impl Iterator<Item = (Range<usize>, String)> + '_ {
+    let mut chars = text.char_indices().peekable();
+    std::iter::from_fn(move || {
+        while chars.next_if(|(_, c)| !c.is_alphanumeric()).is_some() {}
+        let (start, _) = *chars.peek()?;
+        let mut end = start;
+        let mut token = String::new();
+        while let Some((i, c)) = chars.next_if(|(_, c)| c.is_alphanumeric()) {
+            end = i + c.len_utf8();
+            for lower in c.to_lowercase() {
+                token.push(match lower {
+                    'ё' => 'е',
+                    other => other,
+                });
+            }
+        }
+        Some((start..end, token))
+    })
+}
+
+/// Text of markdown without markup and link targets, blocks on separate lines
+pub fn plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(part) | Event::Code(part) => text += &part,
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::Start(Tag::Item) | Event::End(TagEnd::Paragraph | TagEnd::Heading(_)) => {
+                text.push('\n')
+            }
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+/// Line around the first match with the matches inside it highlighted
+fn snippet(text: &str, ranges: &[Range<usize>]) -> Snippet {
+    let Some(first) = ranges.first() else {
+        return Snippet {
+            text: String::new(),
+            highlights: Vec::new(),
+        };
+    };
+    let start = text[..first.start]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT - 1)
+        .map_or(0, |(i, _)| i);
+    let end = text[first.end..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT)
+        .map_or(text.len(), |(i, _)| first.end + i);
+    let prefix = if start > 0 { "…" } else { "" };
+    let mut snippet = format!("{}{}", prefix, &text[start..end]).replace('\n', " ");
+    if end < text.len() {
+        snippet += "…";
+    }
+    let highlights = ranges
+        .iter()
+        .filter(|range| range.start >= start && range.end <= end)
+        .map(|range| {
+            let shift = prefix.len() + range.start - start;
+            shift..shift + range.len()
+        })
+        .collect();
+    Snippet {
+        text: snippet,
+        highlights,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(descriptions: &[(&str, &str)]) -> DocsIndex {
+        let programms = descriptions
+            .iter()
+            .map(|(name, description)| {
+                let prog = Programm {
+                    name: name.to_string(),
+                    description_md: description.to_string(),
+                    ..Default::default()
+                };
+                (name.to_string(), prog)
+            })
+            .collect();
+        DocsIndex::build(&programms)
+    }
+
+    #[test]
+    fn cyrillic_words_are_tokens() {
+        let text = "Ёлка, ещё 2 раза!";
+        let tokens: Vec<(Range<usize>, String)> = tokenize(text).collect();
+        let words: Vec<&str> = tokens.iter().map(|(_, token)| token.as_str()).collect();
+        assert_eq!(words, vec!["елка", "еще", "2", "раза"]);
+        let originals: Vec<&str> = tokens
+            .iter()
+            .map(|(range, _)| &text[range.clone()])
+            .collect();
+        assert_eq!(originals, vec!["Ёлка", "ещё", "2", "раза"]);
+    }
+
+    #[test]
+    fn words_match_as_prefixes() {
+        let docs = index(&[
+            ("rustc", "Компилятор языка **Rust**"),
+            ("zig", "Компилятор и язык Zig, ещё и сборщик C"),
+        ]);
+        let found = |query: &str| -> Vec<(String, usize)> {
+            docs.search(query)
+                .into_iter()
+                .map(|hit| (hit.programm, hit.score))
+                .collect()
+        };
+        assert_eq!(
+            found("компилят"),
+            vec![("rustc".to_string(), 1), ("zig".to_string(), 1)]
+        );
+        // Whole word counts twice
+        assert_eq!(
+            found("язык"),
+            vec![("zig".to_string(), 2), ("rustc".to_string(), 1)]
+        );
+        assert_eq!(found("еще rust"), Vec::new());
+        assert_eq!(found("ЕЩЁ сбор"), vec![("zig".to_string(), 3)]);
+    }
+
+    #[test]
+    fn snippet_highlights_follow_the_ellipsis() {
+        let text = format!(
+            "{} Сборщик мусора и {} конец",
+            "слово ".repeat(20),
+            "x ".repeat(30)
+        );
+        let docs = index(&[("gc", &text)]);
+        let hits = docs.search("сборщик");
+        let snippet = &hits[0].snippet;
+        assert!(snippet.text.starts_with('…'));
+        assert!(snippet.text.ends_with('…'));
+        let highlighted: Vec<&str> = snippet
+            .highlights
+            .iter()
+            .map(|range| &snippet.text[range.clone()])
+            .collect();
+        assert_eq!(highlighted, vec!["Сборщик"]);
+    }
+}
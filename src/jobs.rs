@@ -0,0 +1,779 @@
+use crate::{
+    archive::{self, ArchiveSpec},
+    download::{self, DownloadSpec},
+    env,
+    history::{self, Outcome},
+    packages::PackageCommand,
+    paths, run_script, run_script_captured,
+    script::{self, Completion, RunPolicy, Script},
+    Programm, ProgrammManipulation,
+};
+use iced::futures::{executor, SinkExt, Stream};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Clone)]
+pub enum JobEvent {
+    Progress { downloaded: u64, total: Option<u64> },
+    Finished(Result<Completion, JobError>),
+}
+
+#[derive(Debug, Clone)]
+pub enum JobError {
+    /// Manipulation itself failed
+    Failed(String),
+    /// Manipulation succeeded, but `verify` commands didn't
+    Broken(String),
+}
+
+/// How jobs run, the same for all of them
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JobOptions {
+    /// Temporary working directory of a failed job is left for inspection
+    pub keep_artifacts_on_failure: bool,
+}
+
+impl JobError {
+    fn with_note(self, note: &str) -> JobError {
+        match self {
+            JobError::Failed(e) => JobError::Failed(format!("{}; {}", e, note)),
+            JobError::Broken(e) => JobError::Broken(format!("{}; {}", e, note)),
+        }
+    }
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::Failed(e) | JobError::Broken(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Single action of a job, jobs are planned first so dry run shows exactly what would run
+pub enum Step {
+    /// External command run directly: package manager or elevated script
+    Package(PackageCommand, RunPolicy),
+    /// Script run by pwsh in a new window
+    Script(Script),
+    /// Installer downloaded into the cache, verified and run
+    Installer(DownloadSpec),
+    InstallArchive(ArchiveSpec),
+    UninstallArchive,
+    ApplyEnv,
+    RevertEnv,
+    /// Command which must succeed, run by the platform shell
+    Verify(Script),
+}
+
+/// Changes made by the running job, undone in reverse order when it fails
+#[derive(Default)]
+struct Journal {
+    steps: Vec<Undo>,
+}
+
+enum Undo {
+    /// Version extracted by this job, `previous` was current before it
+    ArchiveVersion {
+        version: String,
+        previous: Option<String>,
+    },
+    /// Artifact which wasn't in the cache before this job, only its partial download or a
+    /// file failing verification is removed, a verified one stays for the next attempt
+    DownloadedFile { path: PathBuf, sha256: String },
+}
+
+/// Directory scripts and installers of a job run in
+struct Workdir {
+    path: PathBuf,
+    /// Created for this job only and removed when it ends
+    temporary: bool,
+}
+
+impl Workdir {
+    /// Programm `workdir` with placeholders resolved, fresh temporary directory without it
+    fn for_job(prog: &Programm) -> Result<Workdir, String> {
+        Ok(match &prog.workdir {
+            Some(dir) => Workdir {
+                path: PathBuf::from(env::expand(dir, &variables(prog))?),
+                temporary: false,
+            },
+            None => {
+                let started = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+                let name = prog.name.replace(['/', '\\', ':'], "_");
+                Workdir {
+                    path: paths::jobs_dir().join(format!("{}-{}", name, started)),
+                    temporary: true,
+                }
+            }
+        })
+    }
+
+    fn create(&self) -> Result<(), String> {
+        fs::create_dir_all(&self.path).map_err(|e| {
+            format!(
+                "Can't create working directory {}: {}",
+                self.path.display(),
+                e
+            )
+        })
+    }
+
+    fn remove(&self) {
+        if self.temporary {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+impl Journal {
+    /// Remembers the artifact when it's about to be downloaded, cached ones are kept
+    fn record_download(&mut self, spec: &DownloadSpec) {
+        let (Ok(path), Ok(sha256)) = (spec.cached_path(&paths::cache_dir()), spec.checksum())
+        else {
+            return;
+        };
+        if !path.exists() {
+            self.steps.push(Undo::DownloadedFile { path, sha256 });
+        }
+    }
+}
+
+/// Runs the manipulation on a background thread, streaming its progress
+pub fn run(
+    prog: Programm,
+    manipulation: ProgrammManipulation,
+    options: JobOptions,
+) -> impl Stream<Item = JobEvent> {
+    iced::stream::channel(16, move |mut output| async move {
+        let mut progress_output = output.clone();
+        thread::spawn(move || {
+            let result = execute(&prog, manipulation, options, &mut |downloaded, total| {
+                // Progress is only informational, skip it when GUI can't keep up
+                let _ = progress_output.try_send(JobEvent::Progress { downloaded, total });
+            });
+            let _ = executor::block_on(output.send(JobEvent::Finished(result)));
+        });
+    })
+}
+
+/// Whether the manipulation can be planned at all, without running anything
+pub fn check(prog: &Programm, manipulation: ProgrammManipulation) -> Result<(), String> {
+    plan(prog, manipulation, &Workdir::for_job(prog)?.path).map(|_| ())
+}
+
+/// Steps of the manipulation in the order they run, scripts get `workdir` resolved
+fn plan(
+    prog: &Programm,
+    manipulation: ProgrammManipulation,
+    workdir: &Path,
+) -> Result<Vec<Step>, String> {
+    if !prog.is_supported() {
+        return Err(format!(
+            "{} isn't supported on {}",
+            prog.name,
+            std::env::consts::OS
+        ));
+    }
+    let elevate = prog.needs_elevation();
+    let command_step = |command: PackageCommand, policy: RunPolicy| -> Result<Step, String> {
+        Ok(Step::Package(
+            if elevate {
+                command.elevated()?
+            } else {
+                command
+            },
+            policy,
+        ))
+    };
+    // Elevated scripts run through the platform shell inside the elevation helper
+    let script_step = |script: &Script| -> Result<Step, String> {
+        let script = expand_script(prog, script, workdir)?;
+        if elevate {
+            command_step(PackageCommand::shell(script.run), script.policy)
+        } else {
+            Ok(Step::Script(script))
+        }
+    };
+    let mut steps = Vec::new();
+    if let Some(package) = &prog.package {
+        let (backend, id) = (package.manager.backend(), &package.id);
+        let commands = match manipulation {
+            ProgrammManipulation::Install => vec![backend.install(id)?],
+            ProgrammManipulation::Reinstall => vec![backend.uninstall(id)?, backend.install(id)?],
+            ProgrammManipulation::Uninstall => vec![backend.uninstall(id)?],
+            ProgrammManipulation::Upgrade => vec![backend.upgrade(id)?],
+            ProgrammManipulation::Repair => backend.repair(id)?,
+        };
+        for command in commands {
+            steps.push(command_step(command, package.policy.clone())?);
+        }
+    } else if let Some(spec) = &prog.archive {
+        spec.check()?;
+        steps.push(match manipulation {
+            ProgrammManipulation::Uninstall => Step::UninstallArchive,
+            // Installed version is replaced with freshly extracted files, which is all
+            // reinstall and repair need, new version is extracted next to the old one
+            _ => Step::InstallArchive(spec.clone()),
+        });
+    } else {
+        if matches!(
+            manipulation,
+            ProgrammManipulation::Uninstall | ProgrammManipulation::Reinstall
+        ) {
+            steps.push(script_step(&prog.deletion)?);
+        }
+        if manipulation != ProgrammManipulation::Uninstall {
+            steps.push(match (&prog.upgrade, &prog.download) {
+                (Some(script), _) if manipulation == ProgrammManipulation::Upgrade => {
+                    script_step(script)?
+                }
+                // Installer is simply run again for everything else
+                (_, Some(spec)) => {
+                    // Bad checksum or file name fails here, before anything runs
+                    spec.cached_path(&paths::cache_dir())?;
+                    Step::Installer(spec.clone())
+                }
+                (_, None) => script_step(&prog.installation)?,
+            });
+        }
+    }
+    if manipulation == ProgrammManipulation::Uninstall {
+        steps.push(Step::RevertEnv);
+    } else {
+        // Upgraded archive lives in a new directory, so variables are applied again
+        if !prog.env.is_empty() {
+            // Placeholders without a value fail here, before anything runs
+            env::describe(&prog.env, &variables(prog))?;
+            steps.push(Step::ApplyEnv);
+        }
+        for script in &prog.verify {
+            steps.push(Step::Verify(expand_script(prog, script, workdir)?));
+        }
+    }
+    Ok(steps)
+}
+
+/// Runs the planned steps in the job working directory, failed installation is rolled back
+pub fn execute(
+    prog: &Programm,
+    manipulation: ProgrammManipulation,
+    options: JobOptions,
+    progress: &mut impl FnMut(u64, Option<u64>),
+) -> Result<Completion, JobError> {
+    let started = Instant::now();
+    let mut entry = history::Entry::begin(&prog.name, manipulation.action());
+    let workdir = Workdir::for_job(prog);
+    let (result, runs) = script::capture(|| {
+        let workdir = workdir.as_ref().map_err(|e| JobError::Failed(e.clone()))?;
+        let steps = plan(prog, manipulation, &workdir.path).map_err(JobError::Failed)?;
+        entry.steps = steps.iter().map(|step| step.describe(prog)).collect();
+        workdir.create().map_err(JobError::Failed)?;
+        run_steps(prog, manipulation, &steps, &workdir.path, progress)
+    });
+    let result = match (result, &workdir) {
+        (Err(e), Ok(workdir)) if options.keep_artifacts_on_failure && workdir.temporary => {
+            Err(e.with_note(&format!("artifacts kept in {}", workdir.path.display())))
+        }
+        (result, workdir) => {
+            if let Ok(workdir) = workdir {
+                workdir.remove();
+            }
+            result
+        }
+    };
+
+    let (outcome, error) = match &result {
+        Ok(Completion::Done) => (Outcome::Ok, None),
+        Ok(Completion::NeedsReboot) => (Outcome::NeedsReboot, None),
+        Err(JobError::Failed(e)) => (Outcome::Failed, Some(e.clone())),
+        Err(JobError::Broken(e)) => (Outcome::Broken, Some(e.clone())),
+    };
+    entry.finish(started.elapsed(), outcome, error, &runs);
+    // Job already ran, missing history entry doesn't change its result
+    if let Err(e) = history::record(entry, &runs) {
+        eprintln!("Can't record history: {}", e);
+    }
+    result
+}
+
+fn run_steps(
+    prog: &Programm,
+    manipulation: ProgrammManipulation,
+    steps: &[Step],
+    workdir: &Path,
+    progress: &mut impl FnMut(u64, Option<u64>),
+) -> Result<Completion, JobError> {
+    let mut journal = Journal::default();
+    let mut completion = Completion::Done;
+    for step in steps {
+        if let Step::Verify(command) = step {
+            // Programm may only work after the reboot
+            if completion == Completion::Done {
+                verify_command(command, Some(workdir)).map_err(JobError::Broken)?;
+            }
+            continue;
+        }
+        match run_step(prog, step, workdir, &mut journal, progress) {
+            Ok(Completion::NeedsReboot) => completion = Completion::NeedsReboot,
+            Ok(Completion::Done) => {}
+            Err(e) => {
+                if manipulation == ProgrammManipulation::Uninstall {
+                    return Err(JobError::Failed(e));
+                }
+                let reverted = rollback(prog, journal, workdir);
+                return Err(JobError::Failed(if reverted.is_empty() {
+                    e
+                } else {
+                    format!("{}; rolled back: {}", e, reverted.join(", "))
+                }));
+            }
+        }
+    }
+    Ok(completion)
+}
+
+/// Describes what the manipulation would do without executing anything
+pub fn dry_run(prog: &Programm, manipulation: ProgrammManipulation) -> String {
+    let mut report = format!("{} {}\n", manipulation.verb(), prog.name);
+    let workdir = match Workdir::for_job(prog) {
+        Ok(workdir) => workdir,
+        Err(e) => return report + &e + "\n",
+    };
+    let steps = match plan(prog, manipulation, &workdir.path) {
+        Ok(steps) => steps,
+        Err(e) => return report + &e + "\n",
+    };
+    report += &format!("Working directory: {}", workdir.path.display());
+    report += match workdir.temporary {
+        true => " (created for this run and removed afterwards)\n",
+        false => "\n",
+    };
+    for (i, step) in steps.iter().enumerate() {
+        report += &format!("{}. {}\n", i + 1, step.describe(prog));
+    }
+    if manipulation != ProgrammManipulation::Uninstall {
+        for step in &prog.rollback {
+            match rollback_command(prog, step, &workdir.path) {
+                Ok(command) => report += &format!("On failure: {}{}\n", command, step.policy),
+                Err(e) => report += &format!("On failure: {}\n", e),
+            }
+        }
+    }
+    report
+}
+
+impl Step {
+    /// What the step runs and with which shell, placeholders resolved
+    pub fn describe(&self, prog: &Programm) -> String {
+        let cache_dir = paths::cache_dir();
+        match self {
+            Step::Package(command, policy) => format!("run {}{}", command, policy),
+            Step::Script(script) => format!(
+                "run in a new {} window: {}",
+                script::shell_command("").0,
+                script
+            ),
+            Step::Installer(spec) => match spec.cached_path(&cache_dir) {
+                Ok(path) => format!(
+                    "download {} into {} and check sha256 {}, then run {}{}",
+                    spec.url,
+                    path.display(),
+                    spec.sha256,
+                    download::installer_command(&path, &spec.args, prog.needs_elevation())
+                        .map(|command| command.to_string())
+                        .unwrap_or_else(|e| e),
+                    spec.policy
+                ),
+                Err(e) => format!("download {}: {}", spec.url, e),
+            },
+            Step::InstallArchive(spec) => format!(
+                "download {} and check sha256 {}, extract into {}, create shims for [{}] in {}",
+                spec.url,
+                spec.sha256,
+                archive::install_dir(&prog.name, &spec.version).display(),
+                spec.bin.join(", "),
+                paths::bin_dir().display()
+            ),
+            Step::UninstallArchive => format!(
+                "remove files extracted for {} [{}] and their shims",
+                prog.name,
+                archive::installed_versions(&prog.name).join(", ")
+            ),
+            Step::ApplyEnv => format!(
+                "change environment: {}",
+                env::describe(&prog.env, &variables(prog))
+                    .map(|changes| changes.join("; "))
+                    .unwrap_or_else(|e| e)
+            ),
+            Step::RevertEnv => "revert environment changes recorded on install".to_string(),
+            Step::Verify(command) => {
+                let (program, args) = script::shell_command(&command.run);
+                format!(
+                    "verify: {} {} \"{}\"{}",
+                    program,
+                    args[..args.len() - 1].join(" "),
+                    command.run,
+                    command.policy
+                )
+            }
+        }
+    }
+}
+
+fn run_step(
+    prog: &Programm,
+    step: &Step,
+    workdir: &Path,
+    journal: &mut Journal,
+    progress: &mut impl FnMut(u64, Option<u64>),
+) -> Result<Completion, String> {
+    match step {
+        Step::Package(command, policy) => command.run(policy, workdir),
+        Step::Script(script) => run_script(script, Some(workdir)),
+        Step::Installer(spec) => {
+            journal.record_download(spec);
+            let artifact = download::fetch(spec, &paths::cache_dir(), progress)?;
+            download::run_artifact(spec, &artifact, prog.needs_elevation(), workdir)
+        }
+        Step::InstallArchive(spec) => {
+            let previous = archive::current_version(&prog.name);
+            journal.record_download(&spec.source());
+            archive::install(&prog.name, spec, progress)?;
+            // Reinstalled version is only swapped in when it's fully installed, nothing to undo
+            if previous.as_ref() != Some(&spec.version) {
+                journal.steps.push(Undo::ArchiveVersion {
+                    version: spec.version.clone(),
+                    previous,
+                });
+            }
+            Ok(Completion::Done)
+        }
+        Step::UninstallArchive => archive::uninstall(&prog.name).map(|()| Completion::Done),
+        Step::ApplyEnv => {
+            env::apply(&prog.name, &prog.env, &variables(prog)).map(|()| Completion::Done)
+        }
+        Step::RevertEnv => env::revert(&prog.name).map(|()| Completion::Done),
+        Step::Verify(command) => verify_command(command, Some(workdir)).map(|()| Completion::Done),
+    }
+}
+
+/// Runs programm `rollback` steps and undoes the journal, returns what was done
+fn rollback(prog: &Programm, journal: Journal, workdir: &Path) -> Vec<String> {
+    let mut reverted = Vec::new();
+    for step in &prog.rollback {
+        let result = rollback_command(prog, step, workdir)
+            .and_then(|command| command.run(&step.policy, workdir));
+        reverted.push(match result {
+            Ok(_) => format!("ran \"{}\"", step.run),
+            Err(e) => e,
+        });
+    }
+    for undo in journal.steps.into_iter().rev() {
+        reverted.push(match undo {
+            Undo::ArchiveVersion { version, previous } => {
+                match archive::discard(&prog.name, &version, previous.as_deref()) {
+                    Ok(()) => format!("removed {} {}", prog.name, version),
+                    Err(e) => format!("can't remove {} {}: {}", prog.name, version, e),
+                }
+            }
+            Undo::DownloadedFile { path, sha256 } => {
+                let mut part = path.clone().into_os_string();
+                part.push(".part");
+                let part = PathBuf::from(part);
+                let removed = if fs::remove_file(&part).is_ok() {
+                    part
+                } else if path.exists()
+                    && download::sha256_file(&path).ok().as_ref() != Some(&sha256)
+                    && fs::remove_file(&path).is_ok()
+                {
+                    path.clone()
+                } else {
+                    continue;
+                };
+                if let Some(dir) = path.parent() {
+                    let _ = fs::remove_dir(dir);
+                }
+                format!("removed unfinished download {}", removed.display())
+            }
+        });
+    }
+    reverted
+}
+
+/// Rollback script run by the platform shell, elevated like the installation was
+fn rollback_command(
+    prog: &Programm,
+    script: &Script,
+    workdir: &Path,
+) -> Result<PackageCommand, String> {
+    let command = PackageCommand::shell(expand_script(prog, script, workdir)?.run);
+    if prog.needs_elevation() {
+        command.elevated()
+    } else {
+        Ok(command)
+    }
+}
+
+/// Runs `verify` commands in order, the first failing one is reported
+pub fn verify(prog: &Programm) -> Result<(), String> {
+    prog.verify
+        .iter()
+        .try_for_each(|command| verify_command(command, None))
+}
+
+fn verify_command(command: &Script, dir: Option<&Path>) -> Result<(), String> {
+    let output = run_script_captured(command, dir)
+        .map_err(|e| format!("Can't run \"{}\": {}", command.run, e))?;
+    match command.policy.completion(output.status) {
+        Some(_) => Ok(()),
+        None => Err(format!(
+            "Verification \"{}\" failed ({}): {}",
+            command.run,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+/// Script with placeholders resolved, `{workdir}` included
+fn expand_script(prog: &Programm, script: &Script, workdir: &Path) -> Result<Script, String> {
+    let mut variables = variables(prog);
+    variables.push(("workdir", workdir.display().to_string()));
+    Ok(Script {
+        run: env::expand(&script.run, &variables)?,
+        policy: script.policy.clone(),
+    })
+}
+
+/// Reminder shown after a job which changed variables only new shells pick up
+pub fn env_hint(prog: &Programm, manipulation: ProgrammManipulation) -> Option<String> {
+    if prog.env.is_empty() || manipulation == ProgrammManipulation::Uninstall {
+        return None;
+    }
+    env::source_hint()
+}
+
+/// Placeholders available in programm `env` values
+pub fn variables(prog: &Programm) -> Vec<(&'static str, String)> {
+    let mut variables = vec![
+        ("bin_dir", paths::bin_dir().display().to_string()),
+        ("data_dir", paths::data_dir().display().to_string()),
+    ];
+    if let Some(spec) = &prog.archive {
+        let install_dir = archive::install_dir(&prog.name, &spec.version);
+        variables.push(("install_dir", install_dir.display().to_string()));
+    }
+    variables
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::download::tests::{serve, sha256};
+    use std::{env, io::Write, process, sync::OnceLock};
+
+    /// Data, cache and config directories of the tests, set once for the whole run
+    fn test_root() -> &'static Path {
+        static ROOT: OnceLock<PathBuf> = OnceLock::new();
+        ROOT.get_or_init(|| {
+            let root = env::temp_dir().join(format!("wtb-jobs-{}", process::id()));
+            let _ = fs::remove_dir_all(&root);
+            for (var, dir) in [
+                ("XDG_DATA_HOME", "data"),
+                ("XDG_CACHE_HOME", "cache"),
+                ("XDG_CONFIG_HOME", "config"),
+            ] {
+                env::set_var(var, root.join(dir));
+            }
+            root
+        })
+    }
+
+    fn programm(name: &str) -> Programm {
+        Programm {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn zip_bytes() -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("tool", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"tool").unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn failed_step_undoes_the_journal_in_reverse() {
+        let root = test_root();
+        let data = zip_bytes();
+        let (url, _) = serve(data.clone());
+        let spec = ArchiveSpec {
+            url: url.replace("tool-setup.exe", "tool-1.0.zip"),
+            sha256: sha256(&data),
+            mirrors: Vec::new(),
+            version: "1.0".to_string(),
+            bin: Vec::new(),
+        };
+        let prog = Programm {
+            archive: Some(spec.clone()),
+            ..programm("rollback-tool")
+        };
+        let cached = spec.source().cached_path(&paths::cache_dir()).unwrap();
+        // Later step fails after spoiling the downloaded archive
+        let failing = format!("echo broken > '{}'; exit 1", cached.display());
+        let steps = [
+            Step::InstallArchive(spec.clone()),
+            Step::Script(Script::from(failing)),
+        ];
+        let workdir = root.join("rollback-work");
+        fs::create_dir_all(&workdir).unwrap();
+
+        let result = run_steps(
+            &prog,
+            ProgrammManipulation::Install,
+            &steps,
+            &workdir,
+            &mut |_, _| {},
+        );
+        let Err(JobError::Failed(error)) = result else {
+            panic!("job didn't fail: {:?}", result);
+        };
+        let reverted = format!(
+            "rolled back: removed rollback-tool 1.0, removed unfinished download {}",
+            cached.display()
+        );
+        assert!(error.ends_with(&reverted), "{}", error);
+        assert_eq!(archive::current_version("rollback-tool"), None);
+        assert!(!archive::install_dir("rollback-tool", "1.0").exists());
+        assert!(!cached.exists());
+    }
+
+    #[test]
+    fn dry_run_lists_the_plan_and_runs_nothing() {
+        let root = test_root();
+        let workdir = root.join("dry-run-work");
+        let prog = Programm {
+            deletion: Script::from("touch {workdir}/deleted".to_string()),
+            installation: Script::from("touch {workdir}/installed".to_string()),
+            verify: vec![Script::from("touch {workdir}/verified".to_string())],
+            rollback: vec![Script::from("touch {workdir}/rolled-back".to_string())],
+            workdir: Some(workdir.display().to_string()),
+            ..programm("dry-run-tool")
+        };
+        let manipulation = ProgrammManipulation::Reinstall;
+        let (report, runs) = script::capture(|| dry_run(&prog, manipulation));
+
+        let planned: Vec<String> = plan(&prog, manipulation, &workdir)
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(i, step)| format!("{}. {}", i + 1, step.describe(&prog)))
+            .collect();
+        let order = ["deleted", "installed", "verified"];
+        assert_eq!(planned.len(), order.len());
+        for (line, file) in planned.iter().zip(order) {
+            assert!(
+                line.contains(&format!("{}/{}", workdir.display(), file)),
+                "{}",
+                line
+            );
+        }
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "Reinstalling dry-run-tool");
+        assert_eq!(
+            lines[1],
+            format!("Working directory: {}", workdir.display())
+        );
+        assert_eq!(lines[2..5], planned);
+        let rollback = format!(
+            "On failure: sh -c \"touch {}/rolled-back\"",
+            workdir.display()
+        );
+        assert_eq!(lines[5..], [rollback]);
+
+        assert!(runs.is_empty());
+        assert!(!workdir.exists());
+    }
+
+    /// Runs the installation script, which tells where it ran, and returns the result
+    /// with the working directory
+    fn run_in_workdir(
+        prog: Programm,
+        fail: bool,
+        options: JobOptions,
+    ) -> (Result<Completion, JobError>, PathBuf) {
+        let seen = test_root().join(format!("{}-workdir", prog.name));
+        let script = format!("pwd > '{}'; exit {}", seen.display(), u8::from(fail));
+        let prog = Programm {
+            installation: Script::from(script),
+            ..prog
+        };
+        let result = execute(
+            &prog,
+            ProgrammManipulation::Install,
+            options,
+            &mut |_, _| {},
+        );
+        let workdir = fs::read_to_string(&seen).unwrap();
+        (result, PathBuf::from(workdir.trim()))
+    }
+
+    #[test]
+    fn temporary_workdir_is_removed() {
+        test_root();
+        for fail in [false, true] {
+            let name = format!("workdir-tool-{}", fail);
+            let (result, workdir) = run_in_workdir(programm(&name), fail, JobOptions::default());
+            assert_eq!(result.is_err(), fail);
+            assert!(
+                workdir.starts_with(paths::jobs_dir()),
+                "{}",
+                workdir.display()
+            );
+            assert!(!workdir.exists());
+        }
+    }
+
+    #[test]
+    fn workdir_is_kept_when_asked() {
+        let root = test_root();
+        let options = JobOptions {
+            keep_artifacts_on_failure: true,
+        };
+        let (result, workdir) = run_in_workdir(programm("kept-tool"), true, options);
+        let Err(JobError::Failed(error)) = result else {
+            panic!("job didn't fail: {:?}", result);
+        };
+        assert!(workdir.is_dir());
+        assert!(error.ends_with(&format!("artifacts kept in {}", workdir.display())));
+        fs::remove_dir_all(&workdir).unwrap();
+
+        // Successful jobs clean up after themselves anyway
+        let (result, workdir) = run_in_workdir(programm("kept-tool-ok"), false, options);
+        assert!(result.is_ok());
+        assert!(!workdir.exists());
+
+        // Own directory of the programm is never removed
+        let own = root.join("own-workdir");
+        for fail in [false, true] {
+            let prog = Programm {
+                workdir: Some(own.display().to_string()),
+                ..programm(&format!("own-workdir-tool-{}", fail))
+            };
+            let (result, workdir) = run_in_workdir(prog, fail, JobOptions::default());
+            assert_eq!(result.is_err(), fail);
+            assert_eq!(workdir, own);
+            assert!(own.is_dir());
+        }
+    }
+}
@@ -0,0 +1,441 @@
+use crate::paths;
+use iced::keyboard::{
+    key::{Named, Physical},
+    Key, Modifiers,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, fs, path::PathBuf};
+
+/// What a key means. Navigation is up to the current menu, commands work everywhere
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAction {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Choose the highlighted item
+    Activate,
+    NextPane,
+    PreviousPane,
+    /// Close the overlay or menu, asks to exit in the programms menu
+    Back,
+    Erase,
+    Search,
+    Palette,
+    Install,
+    Uninstall,
+    Upgrade,
+    Reinstall,
+    Repair,
+    Run,
+    Docs,
+    Folder,
+    Edit,
+    Help,
+    History,
+    CheckUpdates,
+    Exit,
+}
+
+impl KeyAction {
+    /// Navigation keys are handled by the focused menu or overlay
+    pub fn is_navigation(self) -> bool {
+        matches!(
+            self,
+            KeyAction::Up
+                | KeyAction::Down
+                | KeyAction::Left
+                | KeyAction::Right
+                | KeyAction::Activate
+                | KeyAction::NextPane
+                | KeyAction::PreviousPane
+                | KeyAction::Back
+                | KeyAction::Erase
+        )
+    }
+
+    fn name(self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_default()
+    }
+}
+
+const DEFAULT_BINDINGS: [(&str, KeyAction); 24] = [
+    ("Up", KeyAction::Up),
+    ("Down", KeyAction::Down),
+    ("Left", KeyAction::Left),
+    ("Right", KeyAction::Right),
+    ("Enter", KeyAction::Activate),
+    ("Tab", KeyAction::NextPane),
+    ("Shift+Tab", KeyAction::PreviousPane),
+    ("Escape", KeyAction::Back),
+    ("Backspace", KeyAction::Erase),
+    ("/", KeyAction::Search),
+    ("Ctrl+K", KeyAction::Palette),
+    ("Ctrl+I", KeyAction::Install),
+    ("Ctrl+U", KeyAction::Uninstall),
+    ("Ctrl+Shift+U", KeyAction::Upgrade),
+    ("Ctrl+Shift+I", KeyAction::Reinstall),
+    ("Ctrl+Shift+R", KeyAction::Repair),
+    ("Ctrl+R", KeyAction::Run),
+    ("Ctrl+D", KeyAction::Docs),
+    ("Ctrl+O", KeyAction::Folder),
+    ("Ctrl+E", KeyAction::Edit),
+    ("F1", KeyAction::Help),
+    ("Ctrl+H", KeyAction::History),
+    ("F5", KeyAction::CheckUpdates),
+    ("Ctrl+Q", KeyAction::Exit),
+];
+
+/// Key with modifiers, like `Ctrl+Shift+I`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chord {
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+    logo: bool,
+    key: ChordKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ChordKey {
+    Named(Named),
+    /// Lowercase character
+    Char(String),
+}
+
+/// Names of keys in the file, aliases after the names they are shown with
+const NAMED_KEYS: [(&str, Named); 28] = [
+    ("Up", Named::ArrowUp),
+    ("Down", Named::ArrowDown),
+    ("Left", Named::ArrowLeft),
+    ("Right", Named::ArrowRight),
+    ("Enter", Named::Enter),
+    ("Tab", Named::Tab),
+    ("Escape", Named::Escape),
+    ("Backspace", Named::Backspace),
+    ("Space", Named::Space),
+    ("Delete", Named::Delete),
+    ("Insert", Named::Insert),
+    ("Home", Named::Home),
+    ("End", Named::End),
+    ("PageUp", Named::PageUp),
+    ("PageDown", Named::PageDown),
+    ("F1", Named::F1),
+    ("F2", Named::F2),
+    ("F3", Named::F3),
+    ("F4", Named::F4),
+    ("F5", Named::F5),
+    ("F6", Named::F6),
+    ("F7", Named::F7),
+    ("F8", Named::F8),
+    ("F9", Named::F9),
+    ("F10", Named::F10),
+    ("F11", Named::F11),
+    ("F12", Named::F12),
+    ("Esc", Named::Escape),
+];
+
+impl Chord {
+    pub fn parse(text: &str) -> Result<Chord, String> {
+        let mut chord = Chord {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            logo: false,
+            key: ChordKey::Char(String::new()),
+        };
+        // "Ctrl++" binds the plus key
+        let (modifiers, key) = match text.strip_suffix("++") {
+            Some(modifiers) => (modifiers, "+"),
+            None => text.rsplit_once('+').unwrap_or(("", text)),
+        };
+        for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => chord.ctrl = true,
+                "alt" => chord.alt = true,
+                "shift" => chord.shift = true,
+                "logo" | "super" | "win" | "cmd" => chord.logo = true,
+                other => return Err(format!("Unknown modifier \"{}\" in \"{}\"", other, text)),
+            }
+        }
+        chord.key = match NAMED_KEYS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        {
+            Some((_, named)) => ChordKey::Named(*named),
+            None if key.chars().count() == 1 => ChordKey::Char(key.to_lowercase()),
+            None => return Err(format!("Unknown key \"{}\" in \"{}\"", key, text)),
+        };
+        Ok(chord)
+    }
+
+    /// Character without Ctrl, Alt or Logo, it would type into the search otherwise
+    fn is_typing(&self) -> bool {
+        matches!(self.key, ChordKey::Char(_)) && !self.ctrl && !self.alt && !self.logo
+    }
+
+    fn matches(&self, key: &Key, modifiers: Modifiers) -> bool {
+        let key_matches = match (&self.key, key) {
+            (ChordKey::Named(named), Key::Named(pressed)) => named == pressed,
+            (ChordKey::Char(ch), Key::Character(pressed)) => *ch == pressed.to_lowercase(),
+            _ => false,
+        };
+        key_matches && self.modifiers_match(modifiers)
+    }
+
+    /// Shortcuts with Ctrl or Alt stay on their keys with other layouts,
+    /// Ctrl+I is Ctrl+Ш in the Russian one
+    fn matches_physical(&self, physical: &Physical, modifiers: Modifiers) -> bool {
+        let key_matches = match &self.key {
+            ChordKey::Char(ch) if self.ctrl || self.alt => {
+                physical_char(physical).is_some_and(|pressed| *ch == pressed)
+            }
+            _ => false,
+        };
+        key_matches && self.modifiers_match(modifiers)
+    }
+
+    fn modifiers_match(&self, modifiers: Modifiers) -> bool {
+        // Shift is already in typed characters like "?", it only counts for shortcuts
+        let shift_matches = self.is_typing() || self.shift == modifiers.shift();
+        shift_matches
+            && self.ctrl == modifiers.control()
+            && self.alt == modifiers.alt()
+            && self.logo == modifiers.logo()
+    }
+}
+
+/// Character a letter or digit key types in the US layout
+fn physical_char(physical: &Physical) -> Option<String> {
+    let Physical::Code(code) = physical else {
+        return None;
+    };
+    // Codes are named after the US keys, `KeyA` or `Digit1`
+    let name = format!("{:?}", code);
+    let ch = name
+        .strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .filter(|ch| ch.len() == 1)?;
+    Some(ch.to_lowercase())
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (on, name) in [
+            (self.ctrl, "Ctrl+"),
+            (self.alt, "Alt+"),
+            (self.shift, "Shift+"),
+            (self.logo, "Logo+"),
+        ] {
+            if on {
+                f.write_str(name)?;
+            }
+        }
+        match &self.key {
+            ChordKey::Named(named) => {
+                let name = NAMED_KEYS
+                    .iter()
+                    .find(|(_, n)| n == named)
+                    .map_or("?", |(name, _)| name);
+                f.write_str(name)
+            }
+            ChordKey::Char(ch) if self.is_typing() => f.write_str(ch),
+            ChordKey::Char(ch) => f.write_str(&ch.to_uppercase()),
+        }
+    }
+}
+
+/// Keys of every action, built-in defaults changed by `keybindings.json`
+#[derive(Debug)]
+pub struct Keybindings {
+    bindings: Vec<(Chord, KeyAction)>,
+    /// Problems found in the user file: unknown keys or actions and conflicts
+    pub warnings: Vec<String>,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Keybindings {
+            bindings: DEFAULT_BINDINGS
+                .iter()
+                .filter_map(|(chord, action)| Chord::parse(chord).ok().map(|c| (c, *action)))
+                .collect(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+pub fn keybindings_path() -> PathBuf {
+    paths::config_dir().join("keybindings.json")
+}
+
+impl Keybindings {
+    /// Defaults with the user file applied, `{ "Ctrl+I": "install", "F5": "none" }`.
+    /// A chord in the file replaces the default one, `none` unbinds it
+    pub fn load() -> Keybindings {
+        let mut keybindings = Keybindings::default();
+        let path = keybindings_path();
+        let Ok(data) = fs::read_to_string(&path) else {
+            return keybindings;
+        };
+        match serde_json::from_str::<BTreeMap<String, String>>(&data) {
+            Ok(user) => keybindings.apply(&user),
+            Err(e) => keybindings
+                .warnings
+                .push(format!("Can't read {}: {}", path.display(), e)),
+        }
+        keybindings
+    }
+
+    fn apply(&mut self, user: &BTreeMap<String, String>) {
+        // Chord written differently in the file, like "ctrl+i" and "Ctrl+I"
+        let mut seen: Vec<(Chord, &str, &str)> = Vec::new();
+        for (text, action_name) in user {
+            let chord = match Chord::parse(text) {
+                Ok(chord) => chord,
+                Err(e) => {
+                    self.warnings.push(e);
+                    continue;
+                }
+            };
+            let action = match action_name.as_str() {
+                "none" => None,
+                name => match serde_json::from_value::<KeyAction>(name.into()) {
+                    Ok(action) => Some(action),
+                    Err(_) => {
+                        self.warnings
+                            .push(format!("Unknown action \"{}\" for {}", name, text));
+                        continue;
+                    }
+                },
+            };
+            if let Some((_, other_text, other_action)) = seen.iter().find(|(c, _, _)| *c == chord) {
+                self.warnings.push(format!(
+                    "{} is bound to \"{}\" as {} and to \"{}\" as {}, the latter is used",
+                    chord, other_action, other_text, action_name, text
+                ));
+            }
+            let letter =
+                matches!(&chord.key, ChordKey::Char(ch) if ch.chars().all(char::is_alphanumeric));
+            if chord.is_typing() && letter && action.is_some() {
+                self.warnings.push(format!(
+                    "{} has no Ctrl or Alt, it only works outside of the search",
+                    text
+                ));
+            }
+            let replaced = self.bindings.iter().find(|(c, _)| *c == chord);
+            if let Some((_, default)) =
+                replaced.filter(|_| !seen.iter().any(|(c, _, _)| *c == chord))
+            {
+                if action.is_some_and(|action| action != *default) {
+                    self.warnings.push(format!(
+                        "{} was bound to \"{}\" by default, now to \"{}\"",
+                        chord,
+                        default.name(),
+                        action_name
+                    ));
+                }
+            }
+            self.bindings.retain(|(c, _)| *c != chord);
+            if let Some(action) = action {
+                self.bindings.push((chord.clone(), action));
+            }
+            seen.push((chord, text, action_name));
+        }
+        for (_, action) in DEFAULT_BINDINGS {
+            if !self.bindings.iter().any(|(_, a)| *a == action) {
+                self.warnings
+                    .push(format!("\"{}\" isn't bound to any key", action.name()));
+            }
+        }
+    }
+
+    /// Action of the pressed key, `physical` is where it is on the keyboard. While typing
+    /// into the search, keys which type characters type them instead
+    pub fn action(
+        &self,
+        key: &Key,
+        physical: &Physical,
+        modifiers: Modifiers,
+        typing: bool,
+    ) -> Option<KeyAction> {
+        let bindings = || {
+            self.bindings
+                .iter()
+                .filter(|(chord, _)| !(typing && chord.is_typing()))
+        };
+        // Character of the current layout wins over the key it is on
+        bindings()
+            .find(|(chord, _)| chord.matches(key, modifiers))
+            .or_else(|| bindings().find(|(chord, _)| chord.matches_physical(physical, modifiers)))
+            .map(|(_, action)| *action)
+    }
+
+    /// Markdown list of the bindings with the problems of the user file, for Help
+    pub fn reference(&self) -> String {
+        let mut reference = format!(
+            "## Current keybindings\nChanged in `{}`.\n\n",
+            keybindings_path().display()
+        );
+        for (chord, action) in &self.bindings {
+            reference += &format!("- `{}` {}\n", chord, action.name().replace('_', " "));
+        }
+        if !self.warnings.is_empty() {
+            reference += "\nProblems in the keybindings file:\n";
+            for warning in &self.warnings {
+                reference += &format!("- {}\n", warning);
+            }
+        }
+        reference
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iced::keyboard::key::Code;
+
+    fn applied(user: &[(&str, &str)]) -> Keybindings {
+        let mut keybindings = Keybindings::default();
+        let user = user
+            .iter()
+            .map(|(chord, action)| (chord.to_string(), action.to_string()))
+            .collect();
+        keybindings.apply(&user);
+        keybindings
+    }
+
+    #[test]
+    fn replaced_and_unbound_defaults_are_reported() {
+        let keybindings = applied(&[("Ctrl+I", "run"), ("Ctrl+R", "run"), ("F5", "none")]);
+        assert_eq!(
+            keybindings.warnings,
+            vec![
+                "Ctrl+I was bound to \"install\" by default, now to \"run\"",
+                "\"install\" isn't bound to any key",
+                "\"check_updates\" isn't bound to any key",
+            ]
+        );
+        assert!(applied(&[("Ctrl+P", "palette")]).warnings.is_empty());
+    }
+
+    #[test]
+    fn shortcuts_work_with_other_layouts() {
+        let keybindings = Keybindings::default();
+        let cyrillic = Key::Character("ш".into());
+        let physical = Physical::Code(Code::KeyI);
+        assert_eq!(
+            keybindings.action(&cyrillic, &physical, Modifiers::CTRL, false),
+            Some(KeyAction::Install)
+        );
+        // Plain characters are typed as they are
+        assert_eq!(
+            keybindings.action(&cyrillic, &physical, Modifiers::empty(), false),
+            None
+        );
+    }
+}
@@ -1,46 +1,87 @@
 use iced::{
-    border, color, event, keyboard, padding,
-    theme::Palette,
+    border, event,
+    futures::channel::oneshot,
+    keyboard, padding,
     widget::{
-        button, column, container,
+        button, checkbox, column, container,
         markdown::{self, Highlight},
-        row, scrollable, stack, text,
+        pick_list, progress_bar, rich_text, row, scrollable, span, stack, text, text_editor,
+        text_input,
     },
-    Alignment, Background, Border, Element, Event, Length, Padding, Shadow, Subscription, Task,
-    Theme,
+    window, Alignment, Background, Border, Element, Event, Font, Length, Padding, Point, Shadow,
+    Size, Subscription, Task, Theme,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
-    cmp::min,
-    collections::BTreeMap,
+    cmp::{min, Ordering},
+    collections::{BTreeMap, BTreeSet, VecDeque},
     error::Error,
-    fs::File,
+    fs::{self, File},
     io::{BufReader, Read},
-    path::Path,
-    process,
+    path::{Path, PathBuf},
+    process, thread,
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
-fn custom_theme() -> Theme {
-    Theme::custom(
-        "Custom".to_string(),
-        Palette {
-            background: color!(0x2E, 0x34, 0x40),
-            text: color!(0xD8, 0xDE, 0xE9),
-            primary: color!(0x81, 0xA1, 0xC1),
-            success: color!(0xA3, 0xBE, 0x8C),
-            danger: color!(0xBF, 0x61, 0x6A),
-        },
-    )
-}
+mod archive;
+mod cli;
+mod download;
+mod elevation;
+mod env;
+mod focus;
+mod grouping;
+mod history;
+mod index;
+mod jobs;
+mod keybindings;
+mod packages;
+mod palette;
+mod paths;
+mod script;
+mod search;
+mod settings;
+mod templates;
+mod theme;
+
+use archive::ArchiveSpec;
+use download::DownloadSpec;
+use env::EnvValue;
+use focus::{Focus, Pane};
+use grouping::{Grouping, ListView, SortOrder};
+use history::Outcome;
+use jobs::{JobError, JobEvent, JobOptions};
+use keybindings::{KeyAction, Keybindings};
+use packages::{Manager, PackageSpec};
+use script::{Completion, Script};
+use settings::{Retention, Settings};
 
 pub fn main() -> iced::Result {
-    iced::application("Win tool box", WinToolBox::update, WinToolBox::view)
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (settings, settings_error) = match Settings::load() {
+        Ok(settings) => (settings, None),
+        Err(e) => (Settings::default(), Some(e)),
+    };
+    script::set_shell(&settings.shell);
+    if !args.is_empty() {
+        if let Some(e) = &settings_error {
+            eprintln!("{}, default settings are used", e);
+        }
+        process::exit(cli::run(&args));
+    }
+    let app = iced::application("Win tool box", WinToolBox::update, WinToolBox::view)
         .subscription(WinToolBox::subscription)
-        .theme(|_| custom_theme())
+        .theme(WinToolBox::theme)
         .antialiasing(true)
-        .centered()
         .decorations(false)
-        .run_with(WinToolBox::new)
+        .exit_on_close_request(false);
+    let app =
+        match settings.window {
+            Some(geometry) => app.window_size((geometry.width, geometry.height)).position(
+                window::Position::Specific(Point::new(geometry.x, geometry.y)),
+            ),
+            None => app.centered(),
+        };
+    app.run_with(move || WinToolBox::new(settings, settings_error))
 }
 
 #[derive(Debug, Default)]
@@ -57,77 +98,572 @@ struct WinToolBox {
     current_programm: Option<Programm>,
     programms: BTreeMap<String, Programm>,
     config_name: String,
+    config_path: PathBuf,
     status_message: (String, StatusMessageType),
     cur_menu: ControlMenuVariations,
     help_md: Vec<markdown::Item>,
     search_text: String,
     search_selected: bool,
     selected_result_index: usize,
-    search_programms_names: Vec<String>,
+    /// Ranked matches of `search_text`, the order keyboard navigation follows
+    search_results: Vec<search::SearchResult>,
+    /// Keyboard selection in the current menu
+    focus: Focus,
+    keybindings: Keybindings,
+    /// Exit asked for with Escape, waits for confirmation
+    confirm_exit: bool,
+    /// Command palette lines while `search_text` starts with `>`
+    palette: Vec<palette::Suggestion>,
+    /// Full-text index of descriptions, rebuilt whenever programms change
+    docs_index: index::DocsIndex,
+    editor: Option<ProgrammEditor>,
+    /// Downloaded and total bytes of running jobs by programm
+    job_progress: BTreeMap<String, (u64, Option<u64>)>,
+    /// Programms and manipulations of the running jobs, `settings.concurrency` at most
+    running_jobs: Vec<(String, ProgrammManipulation)>,
+    /// Jobs waiting for a free slot
+    job_queue: VecDeque<(String, ProgrammManipulation)>,
+    /// Manipulations are only described instead of being run
+    dry_run: bool,
+    dry_run_report: Option<String>,
+    job_options: JobOptions,
+    /// Recorded jobs, oldest first, loaded when the history menu is opened
+    history: Vec<history::Entry>,
+    history_filter: HistoryFilter,
+    /// Built-in and user themes, read again when the settings menu is opened
+    themes: Vec<theme::AppTheme>,
+    /// Theme files which can't be read
+    theme_errors: Vec<String>,
+    settings: Settings,
+    /// Default config and shell as typed in the settings, applied on Enter
+    default_config_input: String,
+    shell_input: String,
+    /// Relative offset of the programms list, restored when the list is shown again
+    list_scroll: f32,
+    /// Last known size and position of the window, saved on exit
+    window_size: Option<Size>,
+    window_position: Option<Point>,
+    sort: SortOrder,
+    grouping: Grouping,
+    /// Categories whose programms are hidden in the list
+    collapsed: BTreeSet<String>,
+    /// Unix time each programm was last installed, upgraded or run, from the history
+    last_used: BTreeMap<String, u64>,
+    /// Elevation helper found on start, looking it up scans PATH
+    elevation_helper: Option<String>,
+}
+
+/// Filters of the history panel
+struct HistoryFilter {
+    text: String,
+    action: &'static str,
+    result: &'static str,
+}
+
+impl Default for HistoryFilter {
+    fn default() -> Self {
+        HistoryFilter {
+            text: String::new(),
+            action: HISTORY_ALL,
+            result: HISTORY_ALL,
+        }
+    }
+}
+
+const HISTORY_ALL: &str = "all";
+const HISTORY_ACTIONS: [&str; 7] = [
+    HISTORY_ALL,
+    "install",
+    "uninstall",
+    "upgrade",
+    "reinstall",
+    "repair",
+    "run",
+];
+
+#[derive(Debug, Clone, Copy)]
+pub enum HistoryFormat {
+    Json,
+    Csv,
 }
 
-#[derive(Default, Deserialize, Clone)]
+/// Form state of the catalog editor, edits a copy of the programm until saved
+struct ProgrammEditor {
+    original_name: Option<String>,
+    base: Programm,
+    name: String,
+    call: String,
+    docs_link: String,
+    tags: String,
+    category: String,
+    installation: String,
+    deletion: String,
+    detection: String,
+    package: String,
+    upgrade: String,
+    latest_version: String,
+    platforms: String,
+    requires_elevation: bool,
+    description: text_editor::Content,
+    preview: Vec<markdown::Item>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum EditorField {
+    Name,
+    Call,
+    DocsLink,
+    Tags,
+    Category,
+    Installation,
+    Deletion,
+    Detection,
+    Package,
+    Upgrade,
+    LatestVersion,
+    Platforms,
+}
+
+#[derive(Default, Deserialize, Serialize, Clone)]
 pub struct Programm {
     name: String,
     call: Option<String>,
     description_md: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    /// Section of the programms list, programms without one are under "Other"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    category: Option<String>,
     docs_link: Option<String>,
+    #[serde(default)]
     status: ProgrammStatus,
-    installation: String,
-    deletion: String,
+    #[serde(default)]
+    installation: Script,
+    #[serde(default)]
+    deletion: Script,
+    /// Upgrade script, installation is run again when there is none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    upgrade: Option<Script>,
+    /// Script which succeeds only when the programm is installed,
+    /// first version-like word of its output is the installed version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detection: Option<Script>,
+    /// Commands which must succeed after installation, programm is broken otherwise
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    verify: Vec<Script>,
+    /// Scripts undoing a failed installation, run before built-in rollback
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    rollback: Vec<Script>,
+    /// Newest known version, for programms whose package manager can't tell it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    latest_version: Option<String>,
+    /// Package manager backed programm, replaces installation, deletion and detection scripts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    package: Option<PackageSpec>,
+    /// Verified installer to download and run instead of installation script
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    download: Option<DownloadSpec>,
+    /// Portable archive, extracted into managed apps directory instead of running scripts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    archive: Option<ArchiveSpec>,
+    /// Environment changes applied on install and reverted on uninstall,
+    /// `PATH_append`/`PATH_prepend` keys add entries to list variables
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, EnvValue>,
+    /// Installation needs administrator (root) rights, asked for through the elevation helper
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    requires_elevation: bool,
+    /// Directory jobs run scripts and installers in, placeholders like `{data_dir}` allowed.
+    /// Temporary directory removed after each job by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    workdir: Option<String>,
+    /// Operating systems the programm can be installed on (`windows`, `linux`, `macos`), all by default
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    platforms: Vec<String>,
+    #[serde(skip)]
+    installed_version: Option<String>,
+    /// Newest version reported by the package manager
+    #[serde(skip)]
+    available_version: Option<String>,
+    /// Error of the last failed job or verification
+    #[serde(skip)]
+    last_error: Option<String>,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Result of status detection, versions are only known for installed programms
+#[derive(Debug, Clone)]
+pub struct Detection {
+    status: ProgrammStatus,
+    installed_version: Option<String>,
+    available_version: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProgrammManipulation {
     Install,
     Uninstall,
+    Upgrade,
+    /// Uninstall followed by install
+    Reinstall,
+    /// Install over the existing installation
+    Repair,
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ControlMenuVariations {
     HelpMenu,
     #[default]
     ProgrammsMenu,
     ConfigsMenu,
+    EditorMenu,
+    UpdatesMenu,
+    HistoryMenu,
+    SettingsMenu,
     ExitProgramm,
 }
 
+impl ControlMenuVariations {
+    /// Menu the app starts in when this one was open on exit, the editor and exit
+    /// aren't reopened
+    fn restored(self) -> Self {
+        match self {
+            ControlMenuVariations::EditorMenu | ControlMenuVariations::ExitProgramm => {
+                ControlMenuVariations::ProgrammsMenu
+            }
+            menu => menu,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 enum Message {
     SelectProgrammFromList(String),
     RunProgrammDefault,
+    /// Programm started by `RunProgrammDefault` was closed
+    ProgrammRan(String, Result<Completion, String>),
     DescriptionAndDocsLinkClicked(markdown::Url),
     OpenContainingFolder,
     OpenDocsOnline,
     ControlMenuBtn(ControlMenuVariations),
     ManipulateProgramm(ProgrammManipulation),
-    Manipulationresult(ProgrammManipulation, Result<(), String>),
+    UpgradeProgramm(String),
+    UpgradeAll,
+    ToggleDryRun(bool),
+    ToggleKeepArtifacts(bool),
+    CloseDryRun,
+    CheckUpdates,
+    HistoryFilterText(String),
+    HistoryFilterAction(&'static str),
+    HistoryFilterResult(&'static str),
+    ExportHistory(HistoryFormat),
+    OpenLog(PathBuf),
+    SelectTheme(String),
+    OpenThemesFolder,
+    SettingsDefaultConfigInput(String),
+    SettingsDefaultConfig,
+    SettingsUseLoadedConfig,
+    SettingsShellInput(String),
+    SettingsShell,
+    SettingsConcurrency(usize),
+    SettingsDryRun(bool),
+    SettingsLogRetention(Retention),
+    SettingsRememberWindow(bool),
+    Manipulationresult(String, ProgrammManipulation, Result<Completion, JobError>),
+    JobProgress(String, u64, Option<u64>),
+    StatusDetected(String, Detection),
+    CreateConfigFromTemplate(&'static str),
+    LoadConfig(PathBuf),
+    /// Palette command, closes the palette and sends the messages in order
+    PaletteRun(Vec<Message>),
+    PaletteComplete(String),
+    CancelExit,
+    OpenEditor(Option<String>),
+    EditorInput(EditorField, String),
+    EditorToggleElevation(bool),
+    EditorDescriptionAction(text_editor::Action),
+    SaveEditor,
+    CloseEditor,
     AppEvent(Event),
+    WindowEvent(window::Event),
+    ProgrammsScrolled(f32),
+    SortProgramms(SortOrder),
+    GroupProgramms(Grouping),
+    ToggleCategory(String),
 }
 
 impl WinToolBox {
-    fn new() -> (Self, Task<Message>) {
-        let (progs, conf_name) = load_config("programms.json").expect("Can't load a config!");
-        (
-            WinToolBox {
-                current_programm_markdown: Vec::new(),
-                programms: progs,
-                current_programm: None,
-                config_name: conf_name,
-                status_message: ("Ok!".to_string(), StatusMessageType::Success),
-                cur_menu: ControlMenuVariations::ProgrammsMenu,
-                help_md: Vec::new(),
-                search_text: String::new(),
-                search_selected: false,
-                selected_result_index: 0,
-                search_programms_names: Vec::new(),
+    fn new(settings: Settings, settings_error: Option<String>) -> (Self, Task<Message>) {
+        let mut startup_error = settings_error;
+        let mut config_path = settings.default_config.clone();
+        let (progs, conf_name) = match load_config(&config_path) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                startup_error = Some(format!("Can't load {}: {}", config_path.display(), e));
+                config_path = PathBuf::from("programms.json");
+                load_config(&config_path).expect("Can't load a config!")
+            }
+        };
+        let mut toolbox = WinToolBox {
+            current_programm_markdown: Vec::new(),
+            programms: progs,
+            current_programm: None,
+            config_name: conf_name,
+            config_path,
+            status_message: ("Ok!".to_string(), StatusMessageType::Success),
+            cur_menu: ControlMenuVariations::ProgrammsMenu,
+            help_md: Vec::new(),
+            search_text: String::new(),
+            search_selected: false,
+            selected_result_index: 0,
+            search_results: Vec::new(),
+            focus: Focus::default(),
+            keybindings: Keybindings::load(),
+            confirm_exit: false,
+            palette: Vec::new(),
+            docs_index: index::DocsIndex::default(),
+            editor: None,
+            job_progress: BTreeMap::new(),
+            running_jobs: Vec::new(),
+            job_queue: VecDeque::new(),
+            dry_run: settings.dry_run,
+            dry_run_report: None,
+            job_options: JobOptions::default(),
+            history: Vec::new(),
+            history_filter: HistoryFilter::default(),
+            themes: Vec::new(),
+            theme_errors: Vec::new(),
+            default_config_input: settings.default_config.display().to_string(),
+            shell_input: settings.shell.clone(),
+            settings,
+            list_scroll: 0.0,
+            window_size: None,
+            window_position: None,
+            sort: SortOrder::default(),
+            grouping: Grouping::default(),
+            collapsed: BTreeSet::new(),
+            last_used: BTreeMap::new(),
+            // Rights are checked once here, so the view never waits for them
+            elevation_helper: if elevation::is_elevated() {
+                None
+            } else {
+                elevation::helper_name()
             },
-            Task::none(),
+        };
+        toolbox.docs_index = index::DocsIndex::build(&toolbox.programms);
+        (toolbox.themes, toolbox.theme_errors) = theme::available();
+        if !toolbox.keybindings.warnings.is_empty() {
+            toolbox.status_message = (
+                format!(
+                    "{} problems in keybindings, see Help",
+                    toolbox.keybindings.warnings.len()
+                ),
+                StatusMessageType::Error,
+            );
+        }
+        if let Some(chosen) = toolbox
+            .themes
+            .iter()
+            .find(|t| t.name == toolbox.settings.theme)
+        {
+            theme::set(chosen.clone());
+        }
+        if toolbox.settings.log_retention_days > 0 {
+            if let Err(e) = history::prune_logs(toolbox.settings.log_retention_days) {
+                startup_error = Some(e);
+            }
+        }
+        if let Ok(entries) = history::load() {
+            for entry in entries {
+                let done = matches!(entry.result, Outcome::Ok | Outcome::NeedsReboot);
+                if done && marks_use(&entry.action) {
+                    toolbox.last_used.insert(entry.programm, entry.started);
+                }
+            }
+        }
+        let restore = toolbox.restore_ui_state();
+        if let Some(e) = startup_error {
+            toolbox.status_message = (e, StatusMessageType::Error);
+        }
+        let detection = toolbox.detect_statuses();
+        (toolbox, Task::batch([restore, detection]))
+    }
+
+    fn detect_statuses(&mut self) -> Task<Message> {
+        let names: Vec<String> = self.programms.keys().cloned().collect();
+        Task::batch(
+            names
+                .iter()
+                .map(|name| self.detect_programm(name))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Starts background detection of the programm status, nothing to do without a way to detect it
+    /// or while a job holds the programm, it is detected when the job ends
+    fn detect_programm(&mut self, name: &str) -> Task<Message> {
+        let Some(prog) = self.programms.get(name).cloned() else {
+            return Task::none();
+        };
+        if self.is_job_queued(name) {
+            return Task::none();
+        }
+        if !prog.is_supported() {
+            self.update_programm(name, |p| p.status = ProgrammStatus::Unsupported);
+            return Task::none();
+        }
+        if prog.package.is_none() && prog.archive.is_none() && prog.detection.is_none() {
+            return Task::none();
+        }
+        self.update_programm(name, |p| p.status = ProgrammStatus::Detecting);
+        let name = name.to_string();
+        Task::perform(detect_in_background(prog), move |detection| {
+            Message::StatusDetected(name.clone(), detection)
+        })
+    }
+
+    /// Applies the change to the catalog entry and to the selected copy of it
+    fn update_programm(&mut self, name: &str, update: impl Fn(&mut Programm)) {
+        if let Some(prog) = self.programms.get_mut(name) {
+            update(prog);
+        }
+        if let Some(prog) = self.current_programm.as_mut().filter(|p| p.name == name) {
+            update(prog);
+        }
+    }
+
+    /// Queues the jobs, or only describes them in dry run mode
+    fn submit_jobs(&mut self, jobs: Vec<(String, ProgrammManipulation)>) -> Task<Message> {
+        if self.dry_run {
+            let report = jobs
+                .iter()
+                .filter_map(|(name, manipulation)| {
+                    let prog = self.programms.get(name)?;
+                    Some(jobs::dry_run(prog, *manipulation))
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            self.dry_run_report = Some(report);
+            self.cur_menu = ControlMenuVariations::ProgrammsMenu;
+            return Task::none();
+        }
+        Task::batch(
+            jobs.into_iter()
+                .map(|(name, manipulation)| self.enqueue_job(name, manipulation))
+                .collect::<Vec<_>>(),
         )
     }
 
+    /// Adds the job to the queue, the same programm isn't queued twice
+    fn enqueue_job(&mut self, name: String, manipulation: ProgrammManipulation) -> Task<Message> {
+        if !self.is_job_queued(&name) {
+            self.job_queue.push_back((name, manipulation));
+        }
+        self.start_next_job()
+    }
+
+    fn start_next_job(&mut self) -> Task<Message> {
+        let mut started = Vec::new();
+        while self.running_jobs.len() < self.settings.concurrency.max(1) {
+            // Jobs sharing a lock with a running one wait, the ones behind them may start
+            let held: Vec<&str> = (self.running_jobs.iter())
+                .filter_map(|(name, _)| self.programms.get(name))
+                .flat_map(Programm::job_locks)
+                .collect();
+            let free = self.job_queue.iter().position(|(name, _)| {
+                self.programms
+                    .get(name)
+                    .is_none_or(|prog| prog.job_locks().iter().all(|l| !held.contains(l)))
+            });
+            let Some((name, manipulation)) = free.and_then(|i| self.job_queue.remove(i)) else {
+                break;
+            };
+            let Some(prog) = self.programms.get(&name).cloned() else {
+                continue;
+            };
+            let status = match manipulation {
+                ProgrammManipulation::Uninstall => ProgrammStatus::Uninstalling,
+                _ => ProgrammStatus::Installing,
+            };
+            self.update_programm(&name, |p| p.status = status);
+            self.running_jobs.push((name.clone(), manipulation));
+            let job = jobs::run(prog, manipulation, self.job_options);
+            started.push(Task::run(job, move |event| match event {
+                JobEvent::Progress { downloaded, total } => {
+                    Message::JobProgress(name.clone(), downloaded, total)
+                }
+                JobEvent::Finished(res) => {
+                    Message::Manipulationresult(name.clone(), manipulation, res)
+                }
+            }));
+        }
+        Task::batch(started)
+    }
+
+    /// Manipulation of the current programm if its status allows it
+    fn mark_used(&mut self, name: String) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.last_used.insert(name, now);
+    }
+
+    fn is_job_queued(&self, name: &str) -> bool {
+        self.running_jobs
+            .iter()
+            .chain(&self.job_queue)
+            .any(|(n, _)| n == name)
+    }
+
+    fn load_history(&mut self) {
+        match history::load() {
+            Ok(entries) => self.history = entries,
+            Err(e) => self.status_message = (e, StatusMessageType::Error),
+        }
+    }
+
+    /// History entries passing the filters, newest first
+    fn filtered_history(&self) -> Vec<&history::Entry> {
+        let filter = &self.history_filter;
+        let needle = filter.text.to_lowercase();
+        self.history
+            .iter()
+            .rev()
+            .filter(|entry| filter.action == HISTORY_ALL || entry.action == filter.action)
+            .filter(|entry| filter.result == HISTORY_ALL || entry.result.label() == filter.result)
+            .filter(|entry| {
+                needle.is_empty()
+                    || [&entry.programm, &entry.user, &entry.host]
+                        .iter()
+                        .any(|field| field.to_lowercase().contains(&needle))
+                    || entry
+                        .error
+                        .as_ref()
+                        .is_some_and(|error| error.to_lowercase().contains(&needle))
+            })
+            .collect()
+    }
+
+    /// Installed programms behind the latest known version
+    fn outdated_programms(&self) -> Vec<&Programm> {
+        self.programms
+            .values()
+            .filter(|p| p.is_outdated())
+            .collect()
+    }
+
     fn subscription(&self) -> Subscription<Message> {
-        event::listen().map(Message::AppEvent)
+        event::listen_with(|event, status, _window| match event {
+            Event::Window(
+                event @ (window::Event::Opened { .. }
+                | window::Event::Moved(_)
+                | window::Event::Resized(_)
+                | window::Event::CloseRequested),
+            ) => Some(Message::WindowEvent(event)),
+            event => focus::free_keyboard_event(event, status).map(Message::AppEvent),
+        })
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
@@ -185,6 +721,12 @@ impl WinToolBox {
             }
             Message::SelectProgrammFromList(select_prog_name) => {
                 self.search_selected = false;
+                self.cur_menu = ControlMenuVariations::ProgrammsMenu;
+                // Programm chosen elsewhere, like in the search, is shown in the list
+                if let Some(prog) = self.programms.get(&select_prog_name) {
+                    self.collapsed.remove(prog.category());
+                }
+                self.dry_run_report = None;
                 self.selected_result_index = 0;
                 for (prog_name, prog) in &self.programms {
                     if *prog_name == select_prog_name {
@@ -193,71 +735,401 @@ impl WinToolBox {
                         self.current_programm = Some(prog.clone());
                     }
                 }
+                self.focus_current_programm();
                 Task::none()
             }
             Message::RunProgrammDefault => {
                 if let Some(cur_programm) = &self.current_programm {
                     if let Some(call) = &cur_programm.call {
-                        if let Err(e) = run_script_in_new_window(call) {
-                            println!("Error running programm: \"{}\" Error: {}", call, e);
-                            self.status_message =
-                                (format!("Execution failed: {}", e), StatusMessageType::Error);
-                        }
+                        let name = cur_programm.name.clone();
+                        return Task::perform(
+                            run_programm(name.clone(), call.clone()),
+                            move |result| Message::ProgrammRan(name.clone(), result),
+                        );
+                    }
+                }
+                Task::none()
+            }
+            Message::ProgrammRan(name, result) => {
+                match result {
+                    Ok(_) => self.mark_used(name),
+                    Err(e) => {
+                        println!("Error running programm: \"{}\" Error: {}", name, e);
+                        self.status_message =
+                            (format!("Execution failed: {}", e), StatusMessageType::Error);
                     }
                 }
                 Task::none()
             }
             Message::ManipulateProgramm(manipulation) => {
-                if let Some(cur_programm) = &self.current_programm {
-                    let script = match &manipulation {
-                        ProgrammManipulation::Install => cur_programm.installation.clone(),
-                        ProgrammManipulation::Uninstall => cur_programm.deletion.clone(),
-                    };
-                    return Task::perform(
-                        async move { run_script_in_new_window(&script) },
-                        move |res| {
-                            if let Ok(res) = res {
-                                Message::Manipulationresult(manipulation, res)
-                            } else {
-                                Message::Manipulationresult(manipulation, Err("Fail".to_string()))
-                            }
-                        },
+                if let Some(name) = self.current_programm.as_ref().map(|p| p.name.clone()) {
+                    return self.submit_jobs(vec![(name, manipulation)]);
+                }
+                Task::none()
+            }
+            Message::UpgradeProgramm(name) => {
+                self.submit_jobs(vec![(name, ProgrammManipulation::Upgrade)])
+            }
+            Message::UpgradeAll => {
+                let jobs = self
+                    .outdated_programms()
+                    .iter()
+                    .map(|p| (p.name.clone(), ProgrammManipulation::Upgrade))
+                    .collect();
+                self.submit_jobs(jobs)
+            }
+            Message::ToggleKeepArtifacts(keep) => {
+                self.job_options.keep_artifacts_on_failure = keep;
+                Task::none()
+            }
+            Message::ToggleDryRun(dry_run) => {
+                self.dry_run = dry_run;
+                if !dry_run {
+                    self.dry_run_report = None;
+                }
+                Task::none()
+            }
+            Message::CloseDryRun => {
+                self.dry_run_report = None;
+                Task::none()
+            }
+            Message::CheckUpdates => {
+                self.status_message = (
+                    "Checking for updates...".to_string(),
+                    StatusMessageType::Info,
+                );
+                self.detect_statuses()
+            }
+            Message::JobProgress(name, downloaded, total) => {
+                self.job_progress.insert(name, (downloaded, total));
+                Task::none()
+            }
+            Message::Manipulationresult(name, manipulation, result) => {
+                if result.is_ok() && marks_use(manipulation.action()) {
+                    self.mark_used(name.clone());
+                }
+                self.job_progress.remove(&name);
+                self.running_jobs.retain(|(running, _)| *running != name);
+                let (status, error) = match &result {
+                    Err(JobError::Failed(e)) => (ProgrammStatus::Failed, Some(e.clone())),
+                    Err(JobError::Broken(e)) => (ProgrammStatus::Broken, Some(e.clone())),
+                    Ok(Completion::NeedsReboot) => (ProgrammStatus::NeedsReboot, None),
+                    Ok(Completion::Done) if manipulation == ProgrammManipulation::Uninstall => {
+                        (ProgrammStatus::NotInstalled, None)
+                    }
+                    Ok(Completion::Done) => (ProgrammStatus::Installed, None),
+                };
+                self.update_programm(&name, |p| {
+                    p.status = status;
+                    p.last_error = error.clone();
+                });
+                // Versions changed, they are detected again, failure and pending reboot
+                // are kept on screen instead
+                let detection = match status {
+                    ProgrammStatus::Failed | ProgrammStatus::NeedsReboot => Task::none(),
+                    _ => self.detect_programm(&name),
+                };
+                self.status_message = match result {
+                    Ok(Completion::NeedsReboot) => (
+                        format!("{}: reboot to finish", name),
+                        StatusMessageType::Info,
+                    ),
+                    Ok(Completion::Done) => match self
+                        .programms
+                        .get(&name)
+                        .and_then(|prog| jobs::env_hint(prog, manipulation))
+                    {
+                        Some(hint) => (format!("Ok! {}", hint), StatusMessageType::Success),
+                        None => ("Ok!".to_string(), StatusMessageType::Success),
+                    },
+                    Err(e) => (format!("{}: {}", name, e), StatusMessageType::Error),
+                };
+                if matches!(self.cur_menu, ControlMenuVariations::HistoryMenu) {
+                    self.load_history();
+                }
+                Task::batch([detection, self.start_next_job()])
+            }
+            Message::SelectTheme(name) => {
+                if let Some(chosen) = self.themes.iter().find(|t| t.name == name) {
+                    theme::set(chosen.clone());
+                }
+                self.change_settings(|settings| settings.theme = name);
+                Task::none()
+            }
+            Message::SettingsDefaultConfigInput(path) => {
+                self.default_config_input = path;
+                Task::none()
+            }
+            Message::SettingsDefaultConfig => {
+                let path = PathBuf::from(self.default_config_input.trim());
+                self.change_settings(|settings| settings.default_config = path);
+                Task::none()
+            }
+            Message::SettingsUseLoadedConfig => {
+                let path = self.config_path.clone();
+                self.default_config_input = path.display().to_string();
+                self.change_settings(|settings| settings.default_config = path);
+                Task::none()
+            }
+            Message::SettingsShellInput(shell) => {
+                self.shell_input = shell;
+                Task::none()
+            }
+            Message::SettingsShell => {
+                let shell = self.shell_input.trim().to_string();
+                script::set_shell(&shell);
+                self.change_settings(|settings| settings.shell = shell);
+                Task::none()
+            }
+            Message::SettingsConcurrency(concurrency) => {
+                self.change_settings(|settings| settings.concurrency = concurrency);
+                self.start_next_job()
+            }
+            Message::SettingsDryRun(dry_run) => {
+                self.change_settings(|settings| settings.dry_run = dry_run);
+                Task::none()
+            }
+            Message::SettingsLogRetention(Retention(days)) => {
+                self.change_settings(|settings| settings.log_retention_days = days);
+                Task::none()
+            }
+            Message::SettingsRememberWindow(remember) => {
+                self.change_settings(|settings| {
+                    settings.remember_window = remember;
+                    settings.window = settings.window.filter(|_| remember);
+                });
+                Task::none()
+            }
+            Message::OpenThemesFolder => {
+                let dir = theme::themes_dir();
+                let opened = fs::create_dir_all(&dir)
+                    .map_err(|e| e.to_string())
+                    .and_then(|_| opener::open(&dir).map_err(|e| e.to_string()));
+                if let Err(e) = opened {
+                    self.status_message = (
+                        format!("Can't open {}: {}", dir.display(), e),
+                        StatusMessageType::Error,
                     );
                 }
                 Task::none()
             }
-            Message::Manipulationresult(manipulation, result) => {
-                match result {
-                    Ok(()) => {
-                        if let Some(prog) = self.current_programm.as_mut() {
-                            prog.status = match manipulation {
-                                ProgrammManipulation::Install => ProgrammStatus::Installed,
-                                ProgrammManipulation::Uninstall => ProgrammStatus::NotInstalled,
-                            }
-                        }
-                        if let Some(name) = self.current_programm.as_ref().map(|p| p.name.clone()) {
-                            if let Some(prog) = self.programms.get_mut(&name) {
-                                prog.status = match manipulation {
-                                    ProgrammManipulation::Install => ProgrammStatus::Installed,
-                                    ProgrammManipulation::Uninstall => ProgrammStatus::NotInstalled,
-                                }
-                            }
+            Message::HistoryFilterText(text) => {
+                self.history_filter.text = text;
+                Task::none()
+            }
+            Message::HistoryFilterAction(action) => {
+                self.history_filter.action = action;
+                Task::none()
+            }
+            Message::HistoryFilterResult(result) => {
+                self.history_filter.result = result;
+                Task::none()
+            }
+            Message::ExportHistory(format) => {
+                let entries = self.filtered_history();
+                let (path, data) = match format {
+                    HistoryFormat::Json => {
+                        (history::export_path("json"), history::to_json(&entries))
+                    }
+                    HistoryFormat::Csv => (history::export_path("csv"), history::to_csv(&entries)),
+                };
+                let path = std::path::absolute(&path).unwrap_or(path);
+                let written = path
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|()| fs::write(&path, data));
+                self.status_message = match written {
+                    Ok(()) => (
+                        format!("Exported {} entries to {}", entries.len(), path.display()),
+                        StatusMessageType::Success,
+                    ),
+                    Err(e) => (
+                        format!("Can't write {}: {}", path.display(), e),
+                        StatusMessageType::Error,
+                    ),
+                };
+                Task::none()
+            }
+            Message::OpenLog(path) => {
+                if let Err(e) = opener::open(&path) {
+                    self.status_message = (
+                        format!("Can't open {}: {}", path.display(), e),
+                        StatusMessageType::Error,
+                    );
+                }
+                Task::none()
+            }
+            Message::StatusDetected(name, detection) => {
+                // Started before the job, its result is stale and would hide the job status
+                if self.is_job_queued(&name) {
+                    return Task::none();
+                }
+                self.update_programm(&name, |prog| {
+                    prog.installed_version = detection.installed_version.clone();
+                    prog.available_version = detection.available_version.clone();
+                    if detection.error.is_some() {
+                        prog.last_error = detection.error.clone();
+                    }
+                    prog.status = match detection.status {
+                        ProgrammStatus::Installed if prog.is_behind_latest() => {
+                            ProgrammStatus::Outdated
                         }
-                        self.status_message = ("Ok!".to_string(), StatusMessageType::Success);
+                        status => status,
+                    };
+                });
+                Task::none()
+            }
+            Message::CreateConfigFromTemplate(id) => {
+                let Some(template) = templates::find_template(id) else {
+                    return Task::none();
+                };
+                // Second click opens the copy made by the first one
+                let path = template.config_path();
+                let loaded = template
+                    .instantiate_once(&path)
+                    .and_then(|created| Ok((created, load_config(&path)?)));
+                match loaded {
+                    Ok((created, (progs, conf_name))) => {
+                        let verb = if created { "Created" } else { "Loaded" };
+                        self.status_message = (
+                            format!("{} {}", verb, path.display()),
+                            StatusMessageType::Success,
+                        );
+                        self.use_config(progs, conf_name, path)
+                    }
+                    Err(e) => {
+                        self.status_message = (e.to_string(), StatusMessageType::Error);
+                        Task::none()
+                    }
+                }
+            }
+            Message::LoadConfig(path) => match load_config(&path) {
+                Ok((progs, conf_name)) => {
+                    self.status_message = (
+                        format!("Loaded {}", path.display()),
+                        StatusMessageType::Success,
+                    );
+                    self.use_config(progs, conf_name, path)
+                }
+                Err(e) => {
+                    self.status_message = (
+                        format!("Can't load {}: {}", path.display(), e),
+                        StatusMessageType::Error,
+                    );
+                    Task::none()
+                }
+            },
+            Message::PaletteRun(messages) => {
+                self.close_search();
+                Task::batch(
+                    messages
+                        .into_iter()
+                        .map(|message| self.update(message))
+                        .collect::<Vec<_>>(),
+                )
+            }
+            Message::PaletteComplete(completion) => {
+                self.search_selected = true;
+                self.search_text = completion;
+                self.update_search();
+                Task::none()
+            }
+            Message::OpenEditor(name) => {
+                let prog = name.as_ref().and_then(|n| self.programms.get(n));
+                self.editor = Some(ProgrammEditor::new(name.clone(), prog));
+                self.search_selected = false;
+                self.cur_menu = ControlMenuVariations::EditorMenu;
+                Task::none()
+            }
+            Message::EditorInput(field, value) => {
+                if let Some(editor) = self.editor.as_mut() {
+                    match field {
+                        EditorField::Name => editor.name = value,
+                        EditorField::Call => editor.call = value,
+                        EditorField::DocsLink => editor.docs_link = value,
+                        EditorField::Tags => editor.tags = value,
+                        EditorField::Category => editor.category = value,
+                        EditorField::Installation => editor.installation = value,
+                        EditorField::Deletion => editor.deletion = value,
+                        EditorField::Detection => editor.detection = value,
+                        EditorField::Package => editor.package = value,
+                        EditorField::Upgrade => editor.upgrade = value,
+                        EditorField::LatestVersion => editor.latest_version = value,
+                        EditorField::Platforms => editor.platforms = value,
+                    }
+                }
+                Task::none()
+            }
+            Message::EditorToggleElevation(requires_elevation) => {
+                if let Some(editor) = self.editor.as_mut() {
+                    editor.requires_elevation = requires_elevation;
+                }
+                Task::none()
+            }
+            Message::EditorDescriptionAction(action) => {
+                if let Some(editor) = self.editor.as_mut() {
+                    let is_edit = action.is_edit();
+                    editor.description.perform(action);
+                    if is_edit {
+                        editor.preview = markdown::parse(&editor.description.text()).collect();
+                    }
+                }
+                Task::none()
+            }
+            Message::SaveEditor => {
+                let Some(editor) = self.editor.as_mut() else {
+                    return Task::none();
+                };
+                let prog = match editor.build(&self.programms) {
+                    Ok(prog) => prog,
+                    Err(e) => {
+                        editor.error = Some(e);
+                        return Task::none();
                     }
-                    Err(e) => self.status_message = (e, StatusMessageType::Error),
+                };
+                let mut programms = self.programms.clone();
+                if let Some(original_name) = &editor.original_name {
+                    programms.remove(original_name);
+                }
+                programms.insert(prog.name.clone(), prog.clone());
+                if let Err(e) = save_config(&self.config_path, &self.config_name, &programms) {
+                    editor.error = Some(format!("Can't save config: {}", e));
+                    return Task::none();
                 }
+                self.programms = programms;
+                self.docs_index = index::DocsIndex::build(&self.programms);
+                self.current_programm_markdown = markdown::parse(&prog.description_md).collect();
+                let name = prog.name.clone();
+                self.current_programm = Some(prog);
+                self.editor = None;
+                self.cur_menu = ControlMenuVariations::ProgrammsMenu;
+                self.status_message = (
+                    format!("Saved {}", self.config_path.display()),
+                    StatusMessageType::Success,
+                );
+                // Detection, package or platforms may have changed
+                self.detect_programm(&name)
+            }
+            Message::CloseEditor => {
+                self.editor = None;
+                self.cur_menu = ControlMenuVariations::ProgrammsMenu;
                 Task::none()
             }
             Message::ControlMenuBtn(variation) => {
                 self.search_selected = false;
+                self.confirm_exit = false;
+                self.focus = Focus::default();
+                if let ControlMenuVariations::ProgrammsMenu = variation {
+                    self.focus_current_programm();
+                }
                 self.cur_menu = match variation {
                     ControlMenuVariations::ExitProgramm => {
+                        self.save_ui_state();
                         return iced::window::get_latest().and_then(iced::window::close);
                     }
                     ControlMenuVariations::HelpMenu => {
                         let mut readme_text = String::new();
-                        if let Some(mut readme) = File::open("README.md").ok() {
+                        if let Ok(mut readme) = File::open("README.md") {
                             readme.read_to_string(&mut readme_text).unwrap_or_else(|_| {
                                 readme_text = "Failed to read README.md".to_string();
                                 0
@@ -267,109 +1139,92 @@ impl WinToolBox {
                                 "Can't find a README.md file, help and docs stored in it."
                                     .to_string();
                         }
+                        readme_text += "\n\n";
+                        readme_text += &self.keybindings.reference();
                         self.help_md = markdown::parse(&readme_text).collect();
                         ControlMenuVariations::HelpMenu
                     }
+                    ControlMenuVariations::HistoryMenu => {
+                        self.load_history();
+                        ControlMenuVariations::HistoryMenu
+                    }
+                    ControlMenuVariations::SettingsMenu => {
+                        (self.themes, self.theme_errors) = theme::available();
+                        // Text not confirmed with Enter is dropped
+                        self.default_config_input =
+                            self.settings.default_config.display().to_string();
+                        self.shell_input = self.settings.shell.clone();
+                        ControlMenuVariations::SettingsMenu
+                    }
                     other => other,
                 };
+                // Rebuilt list starts at the top, it goes back to where it was
+                if self.cur_menu == ControlMenuVariations::ProgrammsMenu {
+                    return self.snap_programms_list();
+                }
                 Task::none()
             }
-            Message::AppEvent(given_event) => {
-                if let Event::Keyboard(keyboard::Event::KeyPressed {
-                    key: _,
-                    modified_key,
-                    physical_key: _,
-                    location: _,
-                    modifiers: _,
-                    text: _,
-                }) = given_event
-                {
-                    match modified_key {
-                        keyboard::Key::Named(named) => match named {
-                            keyboard::key::Named::ArrowDown => {
-                                self.selected_result_index = min(
-                                    self.selected_result_index + 1,
-                                    if self.search_programms_names.len() > 0 {
-                                        self.search_programms_names.len() - 1
-                                    } else {
-                                        0
-                                    },
-                                );
-                            }
-                            keyboard::key::Named::ArrowUp => {
-                                if self.selected_result_index != 0 {
-                                    self.selected_result_index -= 1;
-                                }
-                            }
-                            keyboard::key::Named::Enter => {
-                                let avaliable_progs = self.programms_startswith(&self.search_text);
-                                if let Some(name) = avaliable_progs.get(min(
-                                    self.selected_result_index,
-                                    if self.search_programms_names.len() > 0 {
-                                        self.search_programms_names.len() - 1
-                                    } else {
-                                        0
-                                    },
-                                )) {
-                                    return self
-                                        .update(Message::SelectProgrammFromList(name.clone()));
-                                } else {
-                                    self.search_selected = false;
-                                    self.search_text.clear();
-                                    self.selected_result_index = 0;
-                                }
-                            }
-                            keyboard::key::Named::Backspace => {
-                                self.search_programms_names =
-                                    self.programms_startswith(&self.search_text);
-                                self.search_text.pop();
-                            }
-                            keyboard::key::Named::Escape => {
-                                if self.search_selected {
-                                    self.search_selected = false;
-                                    self.search_text.clear();
-                                    self.selected_result_index = 0;
-                                } else {
-                                    return self.update(Message::ControlMenuBtn(
-                                        ControlMenuVariations::ExitProgramm,
-                                    ));
-                                }
-                            }
-                            keyboard::key::Named::Space => {
-                                if !self.search_selected {
-                                    self.search_selected = true;
-                                    self.search_text = String::from(" ");
-                                    self.search_programms_names =
-                                        self.programms_startswith(&self.search_text);
-                                } else {
-                                    self.search_text += " ";
-                                    self.search_programms_names =
-                                        self.programms_startswith(&self.search_text);
-                                }
-                            }
-                            _ => {}
-                        },
-                        keyboard::Key::Character(ch) => {
-                            if !self.search_selected {
-                                self.search_selected = true;
-                                self.search_text = ch.to_string();
-                                self.search_programms_names =
-                                    self.programms_startswith(&self.search_text);
-                            } else {
-                                self.search_text += ch.to_string().as_ref();
-                                self.search_programms_names =
-                                    self.programms_startswith(&self.search_text);
-                            }
-                        }
-                        keyboard::Key::Unidentified => {}
-                    }
+            Message::WindowEvent(event) => match event {
+                window::Event::Opened { position, size } => {
+                    self.window_position = position;
+                    self.window_size = Some(size);
+                    self.snap_programms_list()
+                }
+                window::Event::Moved(position) => {
+                    self.window_position = Some(position);
+                    Task::none()
+                }
+                window::Event::Resized(size) => {
+                    self.window_size = Some(size);
+                    Task::none()
+                }
+                window::Event::CloseRequested => {
+                    self.update(Message::ControlMenuBtn(ControlMenuVariations::ExitProgramm))
+                }
+                _ => Task::none(),
+            },
+            Message::ProgrammsScrolled(offset) => {
+                self.list_scroll = offset;
+                Task::none()
+            }
+            Message::SortProgramms(sort) => {
+                self.sort = sort;
+                self.focus_current_programm();
+                Task::none()
+            }
+            Message::GroupProgramms(grouping) => {
+                self.grouping = grouping;
+                self.focus_current_programm();
+                Task::none()
+            }
+            Message::ToggleCategory(category) => {
+                if !self.collapsed.remove(&category) {
+                    self.collapsed.insert(category);
                 }
+                self.focus_current_programm();
+                Task::none()
+            }
+            Message::AppEvent(_) if matches!(self.cur_menu, ControlMenuVariations::EditorMenu) => {
+                Task::none()
+            }
+            Message::CancelExit => {
+                self.confirm_exit = false;
                 Task::none()
             }
+            Message::AppEvent(given_event) => match given_event {
+                Event::Keyboard(keyboard::Event::KeyPressed {
+                    key,
+                    modified_key,
+                    physical_key,
+                    modifiers,
+                    ..
+                }) => self.handle_key(&key, &modified_key, &physical_key, modifiers),
+                _ => Task::none(),
+            },
         }
     }
 
-    fn view(&self) -> Element<Message> {
+    fn view(&self) -> Element<'_, Message> {
         let control_menu_list = row![
             button("[ Programms ]")
                 .on_press(Message::ControlMenuBtn(
@@ -382,6 +1237,18 @@ impl WinToolBox {
             button("[ Config files ]")
                 .on_press(Message::ControlMenuBtn(ControlMenuVariations::ConfigsMenu))
                 .style(menu_buttons_style),
+            button(text(match self.outdated_programms().len() {
+                0 => "[ Updates ]".to_string(),
+                count => format!("[ Updates ({}) ]", count),
+            }))
+            .on_press(Message::ControlMenuBtn(ControlMenuVariations::UpdatesMenu))
+            .style(menu_buttons_style),
+            button("[ History ]")
+                .on_press(Message::ControlMenuBtn(ControlMenuVariations::HistoryMenu))
+                .style(menu_buttons_style),
+            button("[ Settings ]")
+                .on_press(Message::ControlMenuBtn(ControlMenuVariations::SettingsMenu))
+                .style(menu_buttons_style),
             iced::widget::Space::with_width(Length::Fill),
             button("[ Exit ]")
                 .on_press(Message::ControlMenuBtn(ControlMenuVariations::ExitProgramm))
@@ -398,9 +1265,49 @@ impl WinToolBox {
             .width(Length::Fill)
             .height(Length::FillPortion(2));
 
+        // Downloads of all running jobs together, total is known when every job knows it
+        let download_progress: Element<_> = match self
+            .job_progress
+            .values()
+            .copied()
+            .reduce(|(d1, t1), (d2, t2)| (d1 + d2, t1.zip(t2).map(|(t1, t2)| t1 + t2)))
+        {
+            Some((downloaded, total)) => row![
+                text(match total {
+                    Some(total) =>
+                        format!("Downloading {} / {} KiB", downloaded / 1024, total / 1024),
+                    None => format!("Downloading {} KiB", downloaded / 1024),
+                })
+                .size(14),
+                progress_bar(
+                    0.0..=total.unwrap_or(0).max(downloaded).max(1) as f32,
+                    downloaded as f32
+                )
+                .height(10)
+                .width(200),
+            ]
+            .spacing(5)
+            .align_y(Alignment::Center)
+            .into(),
+            None => iced::widget::Space::with_width(0).into(),
+        };
+
+        let running_job = self
+            .running_jobs
+            .iter()
+            .map(|(name, manipulation)| format!("{} {}", manipulation.verb(), name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let running_job = match self.job_queue.len() {
+            0 => running_job,
+            queued => format!("{} ({} queued)", running_job, queued),
+        };
+
         let bottom_info_line = row![
             text(format!("Loaded config: {}", self.config_name)).size(14),
             iced::widget::Space::with_width(Length::Fill),
+            text(running_job).size(14),
+            download_progress,
             match &self.status_message {
                 (message, StatusMessageType::Error) => text(message).size(14).color(color_error()),
                 (message, StatusMessageType::Success) =>
@@ -412,11 +1319,22 @@ impl WinToolBox {
         .spacing(5)
         .width(Length::Fill);
 
-        let cur_menu = container(match self.cur_menu {
+        let scene = match self.cur_menu {
             ControlMenuVariations::HelpMenu => self.help_scene(),
             ControlMenuVariations::ProgrammsMenu => self.main_scene(),
             ControlMenuVariations::ConfigsMenu => self.configs_scene(),
+            ControlMenuVariations::EditorMenu => self.editor_scene(),
+            ControlMenuVariations::UpdatesMenu => self.updates_scene(),
+            ControlMenuVariations::HistoryMenu => self.history_scene(),
+            ControlMenuVariations::SettingsMenu => self.settings_scene(),
             ControlMenuVariations::ExitProgramm => iced::widget::text!("Unreacheable!").into(),
+        };
+        let cur_menu = container(if self.confirm_exit {
+            stack![scene, self.exit_confirmation()].into()
+        } else if self.search_selected {
+            stack![scene, self.search_bar_overlapscene()].into()
+        } else {
+            scene
         })
         .height(Length::FillPortion(37))
         .width(Length::Fill);
@@ -427,44 +1345,130 @@ impl WinToolBox {
             .into()
     }
 
-    fn main_scene(&self) -> Element<Message> {
-        let programms_scrollable_list = scrollable(iced::widget::column(
-            self.programms
-                .iter()
-                .map(|(name, prog)| {
-                    button(prog.name.as_str())
-                        .on_press(Message::SelectProgrammFromList(name.clone()))
+    fn main_scene(&self) -> Element<'_, Message> {
+        let mut list_rows: Vec<Element<_>> = Vec::new();
+        // Row of the programm among the shown ones, what keyboard focus counts
+        let mut shown = 0;
+        for group in self.list_view().groups(&self.programms) {
+            if !group.name.is_empty() {
+                list_rows.push(
+                    button(text(format!(
+                        "{} {} ({}/{})",
+                        if group.collapsed { "+" } else { "-" },
+                        group.name,
+                        group.installed,
+                        group.programms.len()
+                    )))
+                    .on_press(Message::ToggleCategory(group.name.to_string()))
+                    .width(Length::Fill)
+                    .style(menu_buttons_style)
+                    .into(),
+                );
+            }
+            if group.collapsed {
+                continue;
+            }
+            for prog in group.programms {
+                let focused = self.focus.pane == Pane::List
+                    && self.focus.row == shown
+                    && self.current_programm.is_some();
+                shown += 1;
+                list_rows.push(
+                    button(text(format!("{} {}", prog.status.icon(), prog.name)))
+                        .on_press(Message::SelectProgrammFromList(prog.name.clone()))
                         .width(Length::Fill)
-                        .style(programms_buttons_style(prog.status))
-                        .into()
-                })
-                .collect::<Vec<Element<_>>>(),
-        ));
-
-        let programms_list_container = container(programms_scrollable_list)
-            .align_x(Alignment::Center)
-            .padding(5)
-            .style(containers_style)
-            .width(Length::FillPortion(2))
-            .height(Length::Fill);
-
-        let programm_actions = row![
-            button("Run").on_press(Message::RunProgrammDefault),
-            button("Open folder").on_press(Message::OpenContainingFolder),
-            button("Docs").on_press(Message::OpenDocsOnline),
-            if let Some(prog) = &self.current_programm {
-                match prog.status {
-                    ProgrammStatus::Installed => button("Uninstall")
-                        .on_press(Message::ManipulateProgramm(ProgrammManipulation::Uninstall)),
-                    ProgrammStatus::NotInstalled => button("Install")
-                        .on_press(Message::ManipulateProgramm(ProgrammManipulation::Install)),
-                }
-            } else {
-                button("Select a program")
+                        .style(focused_style(programms_buttons_style(prog.status), focused))
+                        .into(),
+                );
             }
+        }
+        let list_options = row![
+            pick_list(SortOrder::ALL, Some(self.sort), Message::SortProgramms)
+                .text_size(14)
+                .width(Length::Fill),
+            pick_list(Grouping::ALL, Some(self.grouping), Message::GroupProgramms)
+                .text_size(14)
+                .width(Length::Fill),
         ]
-        .padding(padding::left(20))
-        .spacing(10);
+        .spacing(5);
+        let programms_scrollable_list = scrollable(column(list_rows))
+            .id(programms_list_id())
+            .on_scroll(|viewport| Message::ProgrammsScrolled(viewport.relative_offset().y));
+
+        let new_programm_btn = button(text("+ New programm").align_x(Alignment::Center))
+            .on_press(Message::OpenEditor(None))
+            .width(Length::Fill)
+            .style(menu_buttons_style);
+
+        let programms_list_container = container(
+            column![
+                list_options,
+                container(programms_scrollable_list).height(Length::Fill),
+                new_programm_btn
+            ]
+            .spacing(5),
+        )
+        .align_x(Alignment::Center)
+        .padding(5)
+        .style(containers_style)
+        .width(Length::FillPortion(2))
+        .height(Length::Fill);
+
+        let action_buttons = self
+            .programm_actions()
+            .into_iter()
+            .enumerate()
+            .map(|(i, (_, label, message))| {
+                let focused = self.focus.pane == Pane::Actions && self.focus.action == i;
+                button(label)
+                    .on_press_maybe(message)
+                    .style(focused_style(button::primary, focused))
+                    .into()
+            })
+            .collect::<Vec<Element<_>>>();
+        let programm_actions = row![
+            row(action_buttons).spacing(10),
+            checkbox("Dry run", self.dry_run).on_toggle(Message::ToggleDryRun),
+            checkbox(
+                "Keep artifacts on failure",
+                self.job_options.keep_artifacts_on_failure
+            )
+            .on_toggle(Message::ToggleKeepArtifacts),
+        ]
+        .padding(padding::left(20))
+        .spacing(10)
+        .align_y(Alignment::Center);
+
+        let status_line: Element<_> = match &self.current_programm {
+            Some(prog) => {
+                let status = match &prog.installed_version {
+                    Some(version) => format!(
+                        "{} {}, {}",
+                        prog.status.icon(),
+                        prog.status.label(),
+                        version
+                    ),
+                    None => format!("{} {}", prog.status.icon(), prog.status.label()),
+                };
+                let elevation: Element<_> =
+                    match prog.elevation_notice(self.elevation_helper.as_deref()) {
+                        Some((notice, false)) => text(notice).size(14).into(),
+                        Some((notice, true)) => text(notice).size(14).color(color_error()).into(),
+                        None => iced::widget::Space::with_width(0).into(),
+                    };
+                row![
+                    text(status).size(14),
+                    elevation,
+                    text(prog.last_error.as_deref().unwrap_or_default())
+                        .size(14)
+                        .color(color_error()),
+                ]
+                .spacing(10)
+                .padding(padding::left(20).top(5))
+                .into()
+            }
+            None => text("Select a program").size(14).into(),
+        };
 
         let programm_actions_container = container(programm_actions)
             .style(containers_style)
@@ -472,23 +1476,47 @@ impl WinToolBox {
             .width(Length::Fill)
             .height(Length::FillPortion(1));
 
-        let description_and_docs_md = markdown::view(
-            &self.current_programm_markdown,
-            markdown::Settings::default(),
-            markdwon_style(),
-        )
-        .map(Message::DescriptionAndDocsLinkClicked);
+        let description_and_docs_md: Element<_> = match &self.dry_run_report {
+            Some(report) => column![
+                row![
+                    text("Dry run, nothing was executed").size(18),
+                    iced::widget::Space::with_width(Length::Fill),
+                    button("Close")
+                        .on_press(Message::CloseDryRun)
+                        .style(menu_buttons_style),
+                ]
+                .align_y(Alignment::Center),
+                scrollable(text(report).font(Font::MONOSPACE).size(14)),
+            ]
+            .spacing(10)
+            .padding(padding::top(10))
+            .into(),
+            None => markdown::view(
+                &self.current_programm_markdown,
+                markdown::Settings::default(),
+                markdwon_style(),
+            )
+            .map(Message::DescriptionAndDocsLinkClicked),
+        };
 
         let description_and_docs_container = container(description_and_docs_md)
             .style(|t| {
                 let default_style = containers_style(t);
-                container::Style { text_color: default_style.text_color, background: default_style.background, border: Border::default(), shadow: default_style.shadow }
+                container::Style {
+                    text_color: default_style.text_color,
+                    background: default_style.background,
+                    border: Border::default(),
+                    shadow: default_style.shadow,
+                }
             })
             .padding(padding::left(20).right(20))
             .height(Length::FillPortion(14));
 
-        let description_elements =
-            iced::widget::column![programm_actions_container, description_and_docs_container,];
+        let description_elements = iced::widget::column![
+            programm_actions_container,
+            status_line,
+            description_and_docs_container,
+        ];
 
         let description_container = container(description_elements)
             .align_x(Alignment::Center)
@@ -499,20 +1527,45 @@ impl WinToolBox {
 
         let main_view = row![programms_list_container, description_container].spacing(8);
 
-        if self.search_selected {
-            stack![main_view, self.search_bar_overlapscene()].into()
-        } else {
-            stack![main_view].into()
-        }
+        main_view.into()
     }
 
-    fn search_bar_overlapscene(&self) -> Element<Message> {
+    fn exit_confirmation(&self) -> Element<'_, Message> {
+        let dialog = container(
+            column![
+                text("Exit Win tool box?").size(20),
+                row![
+                    button("[ Exit ] Enter")
+                        .on_press(Message::ControlMenuBtn(ControlMenuVariations::ExitProgramm))
+                        .style(menu_buttons_style),
+                    button("[ Stay ] Esc")
+                        .on_press(Message::CancelExit)
+                        .style(menu_buttons_style),
+                ]
+                .spacing(10),
+            ]
+            .spacing(15)
+            .align_x(Alignment::Center),
+        )
+        .style(containers_style)
+        .padding(20);
+
+        container(dialog)
+            .center(Length::Fill)
+            .style(|t| container::Style {
+                background: Some(Background::Color(theme::current().overlay.0)),
+                ..containers_style(t)
+            })
+            .into()
+    }
+
+    fn search_bar_overlapscene(&self) -> Element<'_, Message> {
         let search_bar = column![
             container(
-                text(if self.search_text.len() > 0 {
+                text(if !self.search_text.is_empty() {
                     &self.search_text
                 } else {
-                    "=>_Programm name_"
+                    "=>_Programm name, tag or description, > for commands_"
                 })
                 .size(18)
                 .align_x(Alignment::Center)
@@ -523,16 +1576,71 @@ impl WinToolBox {
             .style(containers_style)
             .width(Length::Fill)
             .height(Length::FillPortion(1)),
-            container(scrollable(column(
-                self.search_programms_names
-                    .iter()
-                    .map(|name| button(name.as_str())
-                        .on_press(Message::SelectProgrammFromList(name.to_owned()))
-                        .width(Length::Fill)
-                        .style(programms_buttons_style(ProgrammStatus::Installed))
-                        .into())
-                    .collect::<Vec<Element<_>>>()
-            )))
+            container(
+                scrollable(column(
+                    self.search_results
+                        .iter()
+                        .enumerate()
+                        .map(|(index, result)| {
+                            let mut spans = Vec::new();
+                            if index == self.selected_result_index {
+                                spans.push(span("=> "));
+                            }
+                            spans.extend(result.name.chars().enumerate().map(|(i, c)| {
+                                let highlighted = result.positions.contains(&i);
+                                span(c.to_string())
+                                    .color_maybe(highlighted.then(color_highlight))
+                                    .underline(highlighted)
+                            }));
+                            let matched_in = match &result.matched_in {
+                                search::MatchedIn::Name => String::new(),
+                                search::MatchedIn::Tag(tag) => format!("#{}", tag),
+                                search::MatchedIn::Description(..) => "in description".to_string(),
+                            };
+                            let mut content = column![row![
+                                rich_text(spans).width(Length::Fill),
+                                text(matched_in).size(14),
+                            ]
+                            .align_y(Alignment::Center)];
+                            if let search::MatchedIn::Description(_, snippet) = &result.matched_in {
+                                content = content.push(snippet_text(snippet));
+                            }
+                            button(content)
+                                .on_press(Message::SelectProgrammFromList(result.name.clone()))
+                                .width(Length::Fill)
+                                .style(focused_style(
+                                    programms_buttons_style(ProgrammStatus::Installed),
+                                    index == self.selected_result_index,
+                                ))
+                                .into()
+                        })
+                        .chain(self.palette.iter().enumerate().map(|(index, suggestion)| {
+                            let marker = if index == self.selected_result_index {
+                                "=> "
+                            } else {
+                                ""
+                            };
+                            button(
+                                row![
+                                    text(format!("{}{}", marker, suggestion.label))
+                                        .font(Font::MONOSPACE)
+                                        .width(Length::Fill),
+                                    text(suggestion.description).size(14),
+                                ]
+                                .align_y(Alignment::Center),
+                            )
+                            .on_press(palette_message(suggestion))
+                            .width(Length::Fill)
+                            .style(focused_style(
+                                programms_buttons_style(ProgrammStatus::Unknown),
+                                index == self.selected_result_index,
+                            ))
+                            .into()
+                        }))
+                        .collect::<Vec<Element<_>>>()
+                ))
+                .id(search_results_id())
+            )
             .style(containers_style)
             .width(Length::Fill)
             .height(Length::FillPortion(9))
@@ -547,7 +1655,7 @@ impl WinToolBox {
             let default_container_style = containers_style(_t);
             container::Style {
                 text_color: default_container_style.text_color,
-                background: Some(Background::Color(color!(0x34, 0x3D, 0x4B, 0.6))),
+                background: Some(Background::Color(theme::current().overlay.0)),
                 border: border::Border::default(),
                 shadow: default_container_style.shadow,
             }
@@ -558,41 +1666,1064 @@ impl WinToolBox {
         .into()
     }
 
-    fn help_scene(&self) -> Element<Message> {
+    fn editor_scene(&self) -> Element<'_, Message> {
+        let Some(editor) = &self.editor else {
+            return text("Nothing to edit").into();
+        };
+        let field = |label: &'static str, value: &str, field: EditorField| {
+            column![
+                text(label).size(14),
+                text_input(label, value).on_input(move |v| Message::EditorInput(field, v))
+            ]
+            .spacing(3)
+        };
+
+        let form = column![
+            field("Name", &editor.name, EditorField::Name),
+            field("Call", &editor.call, EditorField::Call),
+            field("Docs link", &editor.docs_link, EditorField::DocsLink),
+            field("Tags (comma separated)", &editor.tags, EditorField::Tags),
+            field("Category", &editor.category, EditorField::Category),
+            field(
+                "Installation script",
+                &editor.installation,
+                EditorField::Installation
+            ),
+            field("Deletion script", &editor.deletion, EditorField::Deletion),
+            field(
+                "Upgrade script (installation is rerun when empty)",
+                &editor.upgrade,
+                EditorField::Upgrade
+            ),
+            field(
+                "Package (manager:id, e.g. winget:Git.Git)",
+                &editor.package,
+                EditorField::Package
+            ),
+            field(
+                "Detection script",
+                &editor.detection,
+                EditorField::Detection
+            ),
+            field(
+                "Latest version",
+                &editor.latest_version,
+                EditorField::LatestVersion
+            ),
+            field(
+                "Platforms (comma separated: windows, linux, macos; all when empty)",
+                &editor.platforms,
+                EditorField::Platforms
+            ),
+            checkbox("Requires administrator rights", editor.requires_elevation)
+                .on_toggle(Message::EditorToggleElevation),
+            text("Description (markdown)").size(14),
+            text_editor(&editor.description)
+                .on_action(Message::EditorDescriptionAction)
+                .height(200),
+        ]
+        .spacing(10)
+        .padding(padding::right(15));
+
+        let editor_actions = row![
+            button("Save")
+                .on_press(Message::SaveEditor)
+                .style(menu_buttons_style),
+            button("Cancel")
+                .on_press(Message::CloseEditor)
+                .style(menu_buttons_style),
+            iced::widget::Space::with_width(Length::Fill),
+            text(editor.error.as_deref().unwrap_or_default()).color(color_error()),
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center);
+
+        let form_container =
+            container(column![scrollable(form).height(Length::Fill), editor_actions].spacing(10))
+                .padding(10)
+                .style(containers_style)
+                .width(Length::FillPortion(1))
+                .height(Length::Fill);
+
+        let preview_container = container(scrollable(
+            markdown::view(
+                &editor.preview,
+                markdown::Settings::default(),
+                markdwon_style(),
+            )
+            .map(Message::DescriptionAndDocsLinkClicked),
+        ))
+        .padding(padding::all(10).left(20).right(20))
+        .style(containers_style)
+        .width(Length::FillPortion(1))
+        .height(Length::Fill);
+
+        row![form_container, preview_container].spacing(8).into()
+    }
+
+    /// Selects the programm, opens the menu and sets the filters saved on last exit.
+    /// The list is scrolled once the window opens
+    fn restore_ui_state(&mut self) -> Task<Message> {
+        let ui = self.settings.ui.clone();
+        self.list_scroll = ui.list_scroll;
+        self.sort = ui.sort;
+        self.grouping = ui.grouping;
+        self.collapsed = ui.collapsed;
+        self.history_filter.text = ui.history_text;
+        if let Some(action) = HISTORY_ACTIONS.iter().find(|a| **a == ui.history_action) {
+            self.history_filter.action = action;
+        }
+        if let Some(result) = history_results()
+            .into_iter()
+            .find(|r| *r == ui.history_result)
+        {
+            self.history_filter.result = result;
+        }
+        let select = match ui.selected_programm {
+            Some(name) if self.programms.contains_key(&name) => {
+                self.update(Message::SelectProgrammFromList(name))
+            }
+            _ => Task::none(),
+        };
+        Task::batch([
+            select,
+            self.update(Message::ControlMenuBtn(ui.menu.restored())),
+        ])
+    }
+
+    fn list_view(&self) -> ListView<'_> {
+        ListView {
+            grouping: self.grouping,
+            sort: self.sort,
+            collapsed: &self.collapsed,
+            last_used: &self.last_used,
+        }
+    }
+
+    /// Keyboard focus follows the selected programm when the list order changes
+    fn focus_current_programm(&mut self) {
+        if let Some(prog) = &self.current_programm {
+            self.focus.row = self
+                .list_view()
+                .visible(&self.programms)
+                .iter()
+                .position(|n| *n == prog.name)
+                .unwrap_or(0);
+        }
+    }
+
+    fn snap_programms_list(&self) -> Task<Message> {
+        scrollable::snap_to(
+            programms_list_id(),
+            scrollable::RelativeOffset {
+                x: 0.0,
+                y: self.list_scroll,
+            },
+        )
+    }
+
+    /// Remembers the window and what is on screen for the next start
+    fn save_ui_state(&mut self) {
+        let ui = settings::UiState {
+            menu: self.cur_menu.restored(),
+            selected_programm: self.current_programm.as_ref().map(|p| p.name.clone()),
+            list_scroll: self.list_scroll,
+            history_text: self.history_filter.text.clone(),
+            history_action: self.history_filter.action.to_string(),
+            history_result: self.history_filter.result.to_string(),
+            sort: self.sort,
+            grouping: self.grouping,
+            collapsed: self.collapsed.clone(),
+        };
+        let window = match (self.window_size, self.window_position) {
+            (Some(size), Some(position)) => Some(settings::WindowGeometry {
+                width: size.width,
+                height: size.height,
+                x: position.x,
+                y: position.y,
+            }),
+            _ => self.settings.window,
+        };
+        self.change_settings(|settings| {
+            settings.ui = ui;
+            settings.window = window.filter(|_| settings.remember_window);
+        });
+    }
+
+    /// Applies the change and writes the settings file
+    fn change_settings(&mut self, change: impl FnOnce(&mut Settings)) {
+        change(&mut self.settings);
+        if let Err(e) = self.settings.save() {
+            self.status_message = (e, StatusMessageType::Error);
+        }
+    }
+
+    fn theme(&self) -> Theme {
+        theme::current().iced()
+    }
+
+    fn settings_scene(&self) -> Element<'_, Message> {
+        let current = &self.settings;
+        let names: Vec<String> = self.themes.iter().map(|t| t.name.clone()).collect();
+        let theme_row = row![
+            pick_list(
+                names,
+                Some(theme::current().name.clone()),
+                Message::SelectTheme
+            ),
+            button("Open themes folder")
+                .on_press(Message::OpenThemesFolder)
+                .style(menu_buttons_style),
+        ]
+        .spacing(10);
+        let theme_hint = text(format!(
+            "Themes are JSON files in {}, they are read when this menu is opened",
+            theme::themes_dir().display()
+        ))
+        .size(14)
+        .color(color_info());
+        let theme_errors = column(
+            self.theme_errors
+                .iter()
+                .map(|e| text(e.as_str()).size(14).color(color_error()).into())
+                .collect::<Vec<Element<_>>>(),
+        )
+        .spacing(5);
+        let default_config = row![
+            text_input("programms.json", &self.default_config_input)
+                .on_input(Message::SettingsDefaultConfigInput)
+                .on_submit(Message::SettingsDefaultConfig)
+                .width(Length::Fixed(300.0)),
+            button("Use loaded config")
+                .on_press(Message::SettingsUseLoadedConfig)
+                .style(menu_buttons_style),
+        ]
+        .spacing(10);
+        let window = row![
+            checkbox("Remember size and position", current.remember_window)
+                .on_toggle(Message::SettingsRememberWindow),
+            text(match current.window {
+                Some(geometry) => format!(
+                    "{}×{} at {}, {}",
+                    geometry.width, geometry.height, geometry.x, geometry.y
+                ),
+                None => "centered".to_string(),
+            })
+            .size(14)
+            .color(color_info()),
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center);
+
+        container(
+            column![
+                text("Settings").size(24),
+                setting_row("Theme", theme_row),
+                theme_hint,
+                theme_errors,
+                setting_row("Default config", default_config),
+                setting_row(
+                    "Shell",
+                    text_input(if cfg!(windows) { "pwsh" } else { "sh" }, &self.shell_input)
+                        .on_input(Message::SettingsShellInput)
+                        .on_submit(Message::SettingsShell)
+                        .width(Length::Fixed(300.0))
+                ),
+                setting_row(
+                    "Parallel jobs",
+                    pick_list(
+                        settings::CONCURRENCY_CHOICES,
+                        Some(current.concurrency),
+                        Message::SettingsConcurrency
+                    )
+                ),
+                setting_row(
+                    "Dry run on start",
+                    checkbox("", current.dry_run).on_toggle(Message::SettingsDryRun)
+                ),
+                setting_row(
+                    "Keep job logs",
+                    pick_list(
+                        settings::RETENTION_CHOICES,
+                        Some(Retention(current.log_retention_days)),
+                        Message::SettingsLogRetention
+                    )
+                ),
+                setting_row("Window", window),
+                text(format!("Saved in {}", settings::settings_path().display()))
+                    .size(14)
+                    .color(color_info()),
+            ]
+            .spacing(15),
+        )
+        .style(containers_style)
+        .padding(20)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .into()
+    }
+
+    fn history_scene(&self) -> Element<'_, Message> {
+        let entries = self.filtered_history();
+        let header = row![
+            text("History").size(24),
+            iced::widget::Space::with_width(Length::Fill),
+            text_input(
+                "Filter by programm, user, host...",
+                &self.history_filter.text
+            )
+            .on_input(Message::HistoryFilterText)
+            .width(Length::Fixed(260.0)),
+            pick_list(
+                HISTORY_ACTIONS,
+                Some(self.history_filter.action),
+                Message::HistoryFilterAction
+            ),
+            pick_list(
+                history_results(),
+                Some(self.history_filter.result),
+                Message::HistoryFilterResult
+            ),
+            button("Export JSON")
+                .on_press(Message::ExportHistory(HistoryFormat::Json))
+                .style(menu_buttons_style),
+            button("Export CSV")
+                .on_press(Message::ExportHistory(HistoryFormat::Csv))
+                .style(menu_buttons_style),
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center);
+
+        let history_list: Element<_> = if entries.is_empty() {
+            text("Nothing recorded yet").size(18).into()
+        } else {
+            scrollable(
+                column(
+                    entries
+                        .into_iter()
+                        .map(|entry| {
+                            let result_color = match entry.result {
+                                Outcome::Ok => color_success(),
+                                Outcome::NeedsReboot => color_info(),
+                                Outcome::Failed | Outcome::Broken => color_error(),
+                            };
+                            let line = row![
+                                text(history::format_time(entry.started))
+                                    .font(Font::MONOSPACE)
+                                    .size(14),
+                                text(format!("{}@{}", entry.user, entry.host))
+                                    .size(14)
+                                    .width(Length::FillPortion(2)),
+                                text(entry.action.as_str()).size(14).width(80),
+                                text(entry.programm.as_str())
+                                    .size(16)
+                                    .width(Length::FillPortion(3)),
+                                text(entry.result.label())
+                                    .size(14)
+                                    .color(result_color)
+                                    .width(100),
+                                text(match entry.exit_code {
+                                    Some(code) => format!("exit {}", code),
+                                    None => String::new(),
+                                })
+                                .size(14)
+                                .width(80),
+                                text(format!("{:.1} s", entry.duration_ms as f64 / 1000.0))
+                                    .size(14)
+                                    .width(70),
+                                button("Log")
+                                    .on_press_maybe(entry.log_file.clone().map(Message::OpenLog))
+                                    .style(menu_buttons_style),
+                            ]
+                            .spacing(10)
+                            .align_y(Alignment::Center);
+                            match &entry.error {
+                                Some(error) => column![
+                                    line,
+                                    text(error.as_str()).size(13).color(color_error())
+                                ]
+                                .into(),
+                                None => line.into(),
+                            }
+                        })
+                        .collect::<Vec<Element<_>>>(),
+                )
+                .spacing(8),
+            )
+            .into()
+        };
+
+        container(column![header, history_list].spacing(15))
+            .style(containers_style)
+            .padding(20)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into()
+    }
+
+    fn help_scene(&self) -> Element<'_, Message> {
         markdown::view(
             &self.help_md,
             markdown::Settings::default(),
             markdwon_style(),
         )
         .map(Message::DescriptionAndDocsLinkClicked)
+    }
+
+    fn configs_scene(&self) -> Element<'_, Message> {
+        let templates_list = column(
+            templates::TEMPLATES
+                .iter()
+                .enumerate()
+                .map(|(i, t)| {
+                    row![
+                        column![text(t.title).size(18), text(t.description).size(14)]
+                            .width(Length::Fill),
+                        button("Create")
+                            .on_press(Message::CreateConfigFromTemplate(t.id))
+                            .style(focused_style(menu_buttons_style, self.focus.row == i)),
+                    ]
+                    .spacing(10)
+                    .align_y(Alignment::Center)
+                    .into()
+                })
+                .collect::<Vec<Element<_>>>(),
+        )
+        .spacing(10);
+
+        container(
+            column![
+                text(format!("Current config: {}", self.config_path.display())).size(18),
+                text("New config from template").size(24),
+                scrollable(templates_list),
+            ]
+            .spacing(15),
+        )
+        .style(containers_style)
+        .padding(20)
+        .width(Length::Fill)
+        .height(Length::Fill)
         .into()
     }
 
-    fn configs_scene(&self) -> Element<Message> {
-        iced::widget::text!("Here will be configuration files menu")
-            .size(30)
+    fn updates_scene(&self) -> Element<'_, Message> {
+        let outdated = self.outdated_programms();
+        let header = row![
+            text("Updates available").size(24),
+            iced::widget::Space::with_width(Length::Fill),
+            button("Check again")
+                .on_press(Message::CheckUpdates)
+                .style(menu_buttons_style),
+            button("Upgrade all")
+                .on_press_maybe((!outdated.is_empty()).then_some(Message::UpgradeAll))
+                .style(menu_buttons_style),
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center);
+
+        let updates_list: Element<_> = if outdated.is_empty() {
+            text("Everything is up to date").size(18).into()
+        } else {
+            scrollable(
+                column(
+                    outdated
+                        .iter()
+                        .enumerate()
+                        .map(|(i, prog)| {
+                            let queued = self.is_job_queued(&prog.name);
+                            row![
+                                text(prog.name.as_str()).size(18).width(Length::Fill),
+                                text(format!(
+                                    "{} -> {}",
+                                    prog.installed_version.as_deref().unwrap_or_default(),
+                                    prog.latest_known_version().unwrap_or_default()
+                                ))
+                                .size(14),
+                                button(if queued { "Queued" } else { "Upgrade" })
+                                    .on_press_maybe(
+                                        (!queued).then(|| {
+                                            Message::UpgradeProgramm(prog.name.clone())
+                                        })
+                                    )
+                                    .style(focused_style(menu_buttons_style, self.focus.row == i,)),
+                            ]
+                            .spacing(10)
+                            .align_y(Alignment::Center)
+                            .into()
+                        })
+                        .collect::<Vec<Element<_>>>(),
+                )
+                .spacing(10),
+            )
+            .into()
+        };
+
+        container(column![header, updates_list].spacing(15))
+            .style(containers_style)
+            .padding(20)
+            .width(Length::Fill)
+            .height(Length::Fill)
             .into()
     }
 
-    fn programms_startswith(&self, startswith: &str) -> Vec<String> {
-        let mut res = Vec::new();
-        for (name, _) in &self.programms {
-            if name.starts_with(startswith) {
-                res.push(name.clone());
+    /// Ranks programms for the changed query, selection goes back to the best match
+    fn update_search(&mut self) {
+        if self.search_text.starts_with('>') {
+            let context = palette::Context {
+                programms: &self.programms,
+                docs: &self.docs_index,
+                dry_run: self.dry_run,
+                keep_artifacts: self.job_options.keep_artifacts_on_failure,
+                themes: &self.themes,
+            };
+            self.palette = palette::suggestions(&self.search_text, &context);
+            self.search_results.clear();
+        } else {
+            self.search_results =
+                search::rank(&self.programms, &self.docs_index, &self.search_text);
+            self.palette.clear();
+        }
+        self.selected_result_index = 0;
+    }
+
+    /// Exit confirmation and the search overlay take keys first, then the current menu.
+    /// Typing opens the palette with `>` anywhere and the search over the programms list
+    fn handle_key(
+        &mut self,
+        key: &keyboard::Key,
+        modified_key: &keyboard::Key,
+        physical_key: &keyboard::key::Physical,
+        modifiers: keyboard::Modifiers,
+    ) -> Task<Message> {
+        let action = self
+            .keybindings
+            .action(key, physical_key, modifiers, self.search_selected);
+        let typed = focus::typed_text(modified_key, modifiers);
+        if self.confirm_exit {
+            return match action {
+                Some(KeyAction::Activate) => {
+                    self.update(Message::ControlMenuBtn(ControlMenuVariations::ExitProgramm))
+                }
+                Some(KeyAction::Back) => self.update(Message::CancelExit),
+                _ => Task::none(),
+            };
+        }
+        if let Some(command) = action.filter(|a| !a.is_navigation()) {
+            return self.command_key(command);
+        }
+        if self.search_selected {
+            return self.search_key(action, typed);
+        }
+        if let Some(typed) = typed {
+            if typed == ">" || matches!(self.cur_menu, ControlMenuVariations::ProgrammsMenu) {
+                self.search_selected = true;
+                self.search_text = typed;
+                self.update_search();
+            }
+            return Task::none();
+        }
+        let Some(action) = action else {
+            return Task::none();
+        };
+        match self.cur_menu {
+            ControlMenuVariations::ProgrammsMenu => self.programms_menu_key(action),
+            ControlMenuVariations::UpdatesMenu => {
+                let upgrades = self
+                    .outdated_programms()
+                    .iter()
+                    .map(|prog| {
+                        (!self.is_job_queued(&prog.name))
+                            .then(|| Message::UpgradeProgramm(prog.name.clone()))
+                    })
+                    .collect();
+                self.list_key(action, upgrades)
+            }
+            ControlMenuVariations::ConfigsMenu => {
+                let templates = templates::TEMPLATES
+                    .iter()
+                    .map(|t| Some(Message::CreateConfigFromTemplate(t.id)))
+                    .collect();
+                self.list_key(action, templates)
+            }
+            _ => self.list_key(action, Vec::new()),
+        }
+    }
+
+    /// Shortcuts working in every menu, programm actions need a selected programm
+    fn command_key(&mut self, action: KeyAction) -> Task<Message> {
+        match action {
+            KeyAction::Search | KeyAction::Palette => {
+                self.search_selected = true;
+                self.search_text = match action {
+                    KeyAction::Palette => ">".to_string(),
+                    _ => String::new(),
+                };
+                self.update_search();
+                return Task::none();
+            }
+            KeyAction::Help => {
+                return self.update(Message::ControlMenuBtn(ControlMenuVariations::HelpMenu))
+            }
+            KeyAction::History => {
+                return self.update(Message::ControlMenuBtn(ControlMenuVariations::HistoryMenu))
             }
+            KeyAction::CheckUpdates => return self.update(Message::CheckUpdates),
+            KeyAction::Exit => {
+                self.confirm_exit = true;
+                return Task::none();
+            }
+            _ => {}
+        }
+        let message = self
+            .programm_actions()
+            .into_iter()
+            .find(|(button, _, _)| *button == action)
+            .and_then(|(_, _, message)| message);
+        match message {
+            Some(message) => self.update(message),
+            None => Task::none(),
         }
-        res
+    }
+
+    fn search_key(&mut self, action: Option<KeyAction>, typed: Option<String>) -> Task<Message> {
+        if let Some(typed) = typed {
+            self.search_text += &typed;
+            self.update_search();
+            return Task::none();
+        }
+        match action {
+            Some(KeyAction::Up) => {
+                self.selected_result_index = self.selected_result_index.saturating_sub(1);
+            }
+            Some(KeyAction::Down) => {
+                self.selected_result_index = min(
+                    self.selected_result_index + 1,
+                    self.search_len().saturating_sub(1),
+                );
+            }
+            Some(KeyAction::Activate) => {
+                let index = min(
+                    self.selected_result_index,
+                    self.search_len().saturating_sub(1),
+                );
+                if let Some(suggestion) = self.palette.get(index) {
+                    return self.update(palette_message(suggestion));
+                } else if let Some(result) = self.search_results.get(index) {
+                    let name = result.name.clone();
+                    return self.update(Message::SelectProgrammFromList(name));
+                }
+                self.close_search();
+            }
+            // Tab completes the palette command
+            Some(KeyAction::NextPane) => {
+                if let Some(suggestion) = self.palette.get(self.selected_result_index) {
+                    let completion = suggestion.completion.clone();
+                    return self.update(Message::PaletteComplete(completion));
+                }
+            }
+            Some(KeyAction::Erase) => {
+                self.search_text.pop();
+                self.update_search();
+            }
+            Some(KeyAction::Back) => self.close_search(),
+            _ => {}
+        }
+        snap_to_row(
+            search_results_id(),
+            self.selected_result_index,
+            self.search_len(),
+        )
+    }
+
+    /// Arrows move in the programms list or between action buttons, Tab switches the pane
+    fn programms_menu_key(&mut self, action: KeyAction) -> Task<Message> {
+        let actions = self.programm_actions();
+        match (self.focus.pane, action) {
+            (_, KeyAction::Back) => self.confirm_exit = true,
+            (pane, KeyAction::NextPane) => self.focus.pane = pane.next(),
+            (pane, KeyAction::PreviousPane) => self.focus.pane = pane.previous(),
+            (Pane::List, KeyAction::Up | KeyAction::Down) => {
+                // First key press highlights the current row instead of moving away from it
+                let visible = self.list_view().visible(&self.programms);
+                let len = visible.len();
+                if self.current_programm.is_some() {
+                    let delta = if action == KeyAction::Up { -1 } else { 1 };
+                    self.focus.move_row(delta, len);
+                }
+                let Some(name) = visible.get(self.focus.row).map(|n| n.to_string()) else {
+                    return Task::none();
+                };
+                let select = self.update(Message::SelectProgrammFromList(name));
+                let scroll = snap_to_row(programms_list_id(), self.focus.row, len);
+                return Task::batch([select, scroll]);
+            }
+            (Pane::List, KeyAction::Activate | KeyAction::Right)
+                if self.current_programm.is_some() =>
+            {
+                self.focus.pane = Pane::Actions;
+            }
+            (Pane::Actions, KeyAction::Left | KeyAction::Up) => {
+                self.focus.move_action(-1, actions.len());
+            }
+            (Pane::Actions, KeyAction::Right | KeyAction::Down) => {
+                self.focus.move_action(1, actions.len());
+            }
+            (Pane::Actions, KeyAction::Activate) => {
+                if let Some((_, _, Some(message))) = actions.into_iter().nth(self.focus.action) {
+                    return self.update(message);
+                }
+            }
+            _ => {}
+        }
+        Task::none()
+    }
+
+    /// Up and Down move the highlighted row, Enter sends its message, Escape goes back
+    /// to the programms menu
+    fn list_key(&mut self, action: KeyAction, rows: Vec<Option<Message>>) -> Task<Message> {
+        match action {
+            KeyAction::Up => self.focus.move_row(-1, rows.len()),
+            KeyAction::Down => self.focus.move_row(1, rows.len()),
+            KeyAction::Activate => {
+                if let Some(Some(message)) = rows.into_iter().nth(self.focus.row) {
+                    return self.update(message);
+                }
+            }
+            KeyAction::Back => {
+                return self.update(Message::ControlMenuBtn(
+                    ControlMenuVariations::ProgrammsMenu,
+                ))
+            }
+            _ => {}
+        }
+        Task::none()
+    }
+
+    /// Buttons above the description with their shortcut actions and messages,
+    /// `None` when not available
+    fn programm_actions(&self) -> Vec<(KeyAction, &'static str, Option<Message>)> {
+        let selected = |message: Message| self.current_programm.as_ref().map(|_| message);
+        let manipulation = |manipulation| {
+            (self.current_programm.as_ref()).and_then(|p| programm_action(p, manipulation))
+        };
+        vec![
+            (KeyAction::Run, "Run", selected(Message::RunProgrammDefault)),
+            (
+                KeyAction::Folder,
+                "Open folder",
+                selected(Message::OpenContainingFolder),
+            ),
+            (KeyAction::Docs, "Docs", selected(Message::OpenDocsOnline)),
+            (
+                KeyAction::Edit,
+                "Edit",
+                self.current_programm
+                    .as_ref()
+                    .map(|p| Message::OpenEditor(Some(p.name.clone()))),
+            ),
+            (
+                KeyAction::Upgrade,
+                "Upgrade",
+                manipulation(ProgrammManipulation::Upgrade),
+            ),
+            (
+                KeyAction::Repair,
+                "Repair",
+                manipulation(ProgrammManipulation::Repair),
+            ),
+            (
+                KeyAction::Reinstall,
+                "Reinstall",
+                manipulation(ProgrammManipulation::Reinstall),
+            ),
+            (
+                KeyAction::Install,
+                "Install",
+                manipulation(ProgrammManipulation::Install),
+            ),
+            (
+                KeyAction::Uninstall,
+                "Uninstall",
+                manipulation(ProgrammManipulation::Uninstall),
+            ),
+        ]
+    }
+
+    /// Lines of the search overlay, palette suggestions or programms
+    fn search_len(&self) -> usize {
+        self.search_results.len() + self.palette.len()
+    }
+
+    fn close_search(&mut self) {
+        self.search_selected = false;
+        self.search_text.clear();
+        self.selected_result_index = 0;
+    }
+
+    /// Replaces loaded programms with the config's ones and detects their statuses
+    fn use_config(
+        &mut self,
+        progs: BTreeMap<String, Programm>,
+        conf_name: String,
+        path: PathBuf,
+    ) -> Task<Message> {
+        self.programms = progs;
+        self.docs_index = index::DocsIndex::build(&self.programms);
+        self.config_name = conf_name;
+        self.config_path = path;
+        self.current_programm = None;
+        self.current_programm_markdown.clear();
+        self.cur_menu = ControlMenuVariations::ProgrammsMenu;
+        self.detect_statuses()
     }
 }
 
-#[derive(Default, Deserialize)]
+impl ProgrammEditor {
+    fn new(original_name: Option<String>, prog: Option<&Programm>) -> Self {
+        let base = prog.cloned().unwrap_or_default();
+        ProgrammEditor {
+            original_name,
+            name: base.name.clone(),
+            call: base.call.clone().unwrap_or_default(),
+            docs_link: base.docs_link.clone().unwrap_or_default(),
+            tags: base.tags.join(", "),
+            category: base.category.clone().unwrap_or_default(),
+            installation: base.installation.run.clone(),
+            deletion: base.deletion.run.clone(),
+            detection: base
+                .detection
+                .as_ref()
+                .map(|s| s.run.clone())
+                .unwrap_or_default(),
+            package: base
+                .package
+                .as_ref()
+                .map(|p| p.to_string())
+                .unwrap_or_default(),
+            upgrade: base
+                .upgrade
+                .as_ref()
+                .map(|s| s.run.clone())
+                .unwrap_or_default(),
+            latest_version: base.latest_version.clone().unwrap_or_default(),
+            platforms: base.platforms.join(", "),
+            requires_elevation: base.requires_elevation,
+            description: text_editor::Content::with_text(&base.description_md),
+            preview: markdown::parse(&base.description_md).collect(),
+            error: None,
+            base,
+        }
+    }
+
+    /// Validates the form and builds the edited programm from it
+    fn build(&self, programms: &BTreeMap<String, Programm>) -> Result<Programm, String> {
+        let non_empty = |s: &str| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+        // Run policies aren't in the form, edited scripts keep them
+        let edited = |run: String, base: &Option<Script>| Script {
+            run,
+            policy: base.as_ref().map(|s| s.policy.clone()).unwrap_or_default(),
+        };
+        let name = non_empty(&self.name).ok_or("Name can't be empty")?;
+        if self.original_name.as_ref() != Some(&name) && programms.contains_key(&name) {
+            return Err(format!("Programm \"{}\" already exists", name));
+        }
+        let docs_link = non_empty(&self.docs_link);
+        if let Some(link) = &docs_link {
+            if !link.starts_with("http://") && !link.starts_with("https://") {
+                return Err("Docs link must start with http:// or https://".to_string());
+            }
+        }
+        let package = match non_empty(&self.package) {
+            Some(package) => {
+                let (manager, id) = package
+                    .split_once(':')
+                    .filter(|(_, id)| !id.trim().is_empty())
+                    .ok_or("Package must look like manager:id, e.g. apt:clang")?;
+                let manager = Manager::from_name(manager.trim())
+                    .ok_or_else(|| format!("Unknown package manager \"{}\"", manager))?;
+                Some(PackageSpec {
+                    manager,
+                    id: id.trim().to_string(),
+                    policy: (self.base.package.as_ref())
+                        .map(|p| p.policy.clone())
+                        .unwrap_or_default(),
+                })
+            }
+            None => None,
+        };
+        let platforms: Vec<String> = self.platforms.split(',').filter_map(non_empty).collect();
+        if let Some(platform) = platforms
+            .iter()
+            .find(|p| !["windows", "linux", "macos"].contains(&p.as_str()))
+        {
+            return Err(format!("Unknown platform \"{}\"", platform));
+        }
+        let installation = non_empty(&self.installation).unwrap_or_default();
+        let deletion = non_empty(&self.deletion).unwrap_or_default();
+        if package.is_none() && self.base.archive.is_none() {
+            if installation.is_empty() && self.base.download.is_none() {
+                return Err("Installation script is required without a package".to_string());
+            }
+            if deletion.is_empty() {
+                return Err("Deletion script is required without a package".to_string());
+            }
+        }
+        Ok(Programm {
+            name,
+            call: non_empty(&self.call),
+            description_md: self.description.text().trim_end().to_string(),
+            tags: self.tags.split(',').filter_map(non_empty).collect(),
+            category: non_empty(&self.category),
+            docs_link,
+            installation: Script {
+                run: installation,
+                policy: self.base.installation.policy.clone(),
+            },
+            deletion: Script {
+                run: deletion,
+                policy: self.base.deletion.policy.clone(),
+            },
+            upgrade: non_empty(&self.upgrade).map(|run| edited(run, &self.base.upgrade)),
+            detection: non_empty(&self.detection).map(|run| edited(run, &self.base.detection)),
+            latest_version: non_empty(&self.latest_version),
+            platforms,
+            requires_elevation: self.requires_elevation,
+            package,
+            ..self.base.clone()
+        })
+    }
+}
+
+impl Programm {
+    fn category(&self) -> &str {
+        self.category
+            .as_deref()
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .unwrap_or(grouping::OTHER)
+    }
+
+    /// Newest version the programm can be upgraded to, if anything knows it
+    fn latest_known_version(&self) -> Option<&str> {
+        self.available_version
+            .as_deref()
+            .or(self.archive.as_ref().map(|spec| spec.version.as_str()))
+            .or(self.latest_version.as_deref())
+    }
+
+    /// Detected version is older than the latest known one
+    fn is_behind_latest(&self) -> bool {
+        matches!(
+            (self.installed_version.as_deref(), self.latest_known_version()),
+            (Some(installed), Some(latest)) if compare_versions(installed, latest) == Ordering::Less
+        )
+    }
+
+    fn is_outdated(&self) -> bool {
+        matches!(self.status, ProgrammStatus::Outdated)
+    }
+
+    /// Programm isn't limited to other platforms and its package manager exists here
+    fn is_supported(&self) -> bool {
+        (self.platforms.is_empty() || self.platforms.iter().any(|p| p == std::env::consts::OS))
+            && self.package.iter().all(|p| p.manager.is_supported())
+    }
+
+    /// Manipulations have to go through the elevation helper
+    fn needs_elevation(&self) -> bool {
+        (self.requires_elevation || self.package.as_ref().is_some_and(|p| p.manager.is_system()))
+            && !elevation::is_elevated()
+    }
+
+    /// What jobs of the programm can't share with other running jobs: a package manager
+    /// holds its database lock, Windows Installer runs one installation at a time and
+    /// environment changes rewrite the same profile
+    fn job_locks(&self) -> Vec<&'static str> {
+        let mut locks: Vec<&'static str> = self.package.iter().map(|p| p.manager.name()).collect();
+        if self.download.is_some() {
+            locks.push("installer");
+        }
+        if !self.env.is_empty() {
+            locks.push("env");
+        }
+        locks
+    }
+
+    /// What the user is asked for before manipulations, `None` when nothing
+    fn elevation_notice(&self, helper: Option<&str>) -> Option<(String, bool)> {
+        if !self.needs_elevation() {
+            return None;
+        }
+        Some(match helper {
+            Some("runas") => (
+                "Needs administrator rights, UAC will ask for them".to_string(),
+                false,
+            ),
+            Some(helper) => (format!("Needs root rights, asked for by {}", helper), false),
+            None => (
+                "Needs root rights, but no sudo, doas or pkexec found".to_string(),
+                true,
+            ),
+        })
+    }
+}
+
+impl ProgrammManipulation {
+    /// Name in the history and on the command line
+    fn action(self) -> &'static str {
+        match self {
+            ProgrammManipulation::Install => "install",
+            ProgrammManipulation::Uninstall => "uninstall",
+            ProgrammManipulation::Upgrade => "upgrade",
+            ProgrammManipulation::Reinstall => "reinstall",
+            ProgrammManipulation::Repair => "repair",
+        }
+    }
+
+    fn verb(self) -> &'static str {
+        match self {
+            ProgrammManipulation::Install => "Installing",
+            ProgrammManipulation::Uninstall => "Uninstalling",
+            ProgrammManipulation::Upgrade => "Upgrading",
+            ProgrammManipulation::Reinstall => "Reinstalling",
+            ProgrammManipulation::Repair => "Repairing",
+        }
+    }
+
+    fn is_allowed(self, status: ProgrammStatus) -> bool {
+        match self {
+            ProgrammManipulation::Upgrade => {
+                matches!(status, ProgrammStatus::Installed | ProgrammStatus::Outdated)
+            }
+            ProgrammManipulation::Repair | ProgrammManipulation::Reinstall => {
+                status.is_installed() || status == ProgrammStatus::Failed
+            }
+            // Both are offered while it isn't known whether the programm is there
+            ProgrammManipulation::Install => matches!(
+                status,
+                ProgrammStatus::NotInstalled | ProgrammStatus::Unknown | ProgrammStatus::Failed
+            ),
+            ProgrammManipulation::Uninstall => {
+                status.is_installed()
+                    || matches!(status, ProgrammStatus::Unknown | ProgrammStatus::Failed)
+            }
+        }
+    }
+}
+
+/// Message doing the manipulation, `None` when the programm status doesn't allow it
+fn programm_action(prog: &Programm, manipulation: ProgrammManipulation) -> Option<Message> {
+    (manipulation.is_allowed(prog.status)).then_some(Message::ManipulateProgramm(manipulation))
+}
+
+#[derive(Default, Deserialize, Serialize)]
 pub struct ConfigLoad {
     name: String,
     programms: Vec<Programm>,
 }
 
 pub fn load_config(
-    config_name: &str,
+    config_name: &Path,
 ) -> Result<(BTreeMap<String, Programm>, String), Box<dyn Error>> {
     let file = File::open(config_name)?;
     let reader = BufReader::new(file);
@@ -604,31 +2735,167 @@ pub fn load_config(
     Ok((res, r.name))
 }
 
-#[derive(Clone, Copy, Deserialize, Default)]
+/// Compares dotted versions numerically, `0.9.1 < 0.12.0`, non-numeric parts as text
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| {
+        v.trim_start_matches('v')
+            .split(['.', '-', '+'])
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (parts(a), parts(b));
+    for (x, y) in a.iter().zip(&b) {
+        let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.cmp(y),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// First version-like word of the text, `2.45.1` from `git version 2.45.1.windows.1`
+pub fn find_version(text: &str) -> Option<String> {
+    text.split_whitespace().find_map(|word| {
+        let word = word
+            .trim_matches(|c: char| !c.is_alphanumeric())
+            .trim_start_matches('v');
+        (word.starts_with(|c: char| c.is_ascii_digit()) && word.contains('.'))
+            .then(|| word.to_string())
+    })
+}
+
+pub fn save_config(
+    config_name: &Path,
+    name: &str,
+    programms: &BTreeMap<String, Programm>,
+) -> Result<(), Box<dyn Error>> {
+    let config = ConfigLoad {
+        name: name.to_string(),
+        programms: programms
+            .values()
+            .cloned()
+            .map(|mut prog| {
+                prog.status = prog.status.persisted();
+                prog
+            })
+            .collect(),
+    };
+    let mut json = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    config.serialize(&mut serde_json::Serializer::with_formatter(
+        &mut json, formatter,
+    ))?;
+    let tmp_path = config_name.with_extension("json.tmp");
+    fs::write(&tmp_path, json)?;
+    fs::rename(&tmp_path, config_name)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
 pub enum ProgrammStatus {
-    Installed,
     #[default]
+    Unknown,
+    Detecting,
+    Installing,
+    Uninstalling,
+    Installed,
     NotInstalled,
+    /// Last job failed, actual state is unknown
+    Failed,
+    /// Installed, but `verify` commands fail
+    Broken,
+    /// Installed version is behind the latest known one
+    Outdated,
+    /// Can't be installed on this platform
+    Unsupported,
+    /// Job succeeded, but a reboot is needed to finish it
+    NeedsReboot,
+}
+
+impl ProgrammStatus {
+    /// Something is installed, even if it doesn't work or isn't the latest
+    pub fn is_installed(self) -> bool {
+        matches!(
+            self,
+            ProgrammStatus::Installed
+                | ProgrammStatus::Broken
+                | ProgrammStatus::Outdated
+                | ProgrammStatus::NeedsReboot
+        )
+    }
+
+    /// Detection or a job is running, no other manipulation is offered
+    pub fn is_busy(self) -> bool {
+        matches!(
+            self,
+            ProgrammStatus::Detecting | ProgrammStatus::Installing | ProgrammStatus::Uninstalling
+        )
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ProgrammStatus::Unknown => "Unknown",
+            ProgrammStatus::Detecting => "Detecting",
+            ProgrammStatus::Installing => "Installing",
+            ProgrammStatus::Uninstalling => "Uninstalling",
+            ProgrammStatus::Installed => "Installed",
+            ProgrammStatus::NotInstalled => "Not installed",
+            ProgrammStatus::Failed => "Failed",
+            ProgrammStatus::Broken => "Broken",
+            ProgrammStatus::Outdated => "Outdated",
+            ProgrammStatus::Unsupported => "Unsupported",
+            ProgrammStatus::NeedsReboot => "Needs reboot",
+        }
+    }
+
+    /// Marker shown before the name in programms list
+    pub fn icon(self) -> &'static str {
+        match self {
+            ProgrammStatus::Unknown => "[?]",
+            ProgrammStatus::Detecting => "[~]",
+            ProgrammStatus::Installing => "[>]",
+            ProgrammStatus::Uninstalling => "[<]",
+            ProgrammStatus::Installed => "[+]",
+            ProgrammStatus::NotInstalled => "[ ]",
+            ProgrammStatus::Failed => "[x]",
+            ProgrammStatus::Broken => "[!]",
+            ProgrammStatus::Outdated => "[^]",
+            ProgrammStatus::Unsupported => "[-]",
+            ProgrammStatus::NeedsReboot => "[*]",
+        }
+    }
+
+    /// Status written to the config, runtime states are detected again on load
+    fn persisted(self) -> ProgrammStatus {
+        match self {
+            ProgrammStatus::Installed | ProgrammStatus::Outdated => ProgrammStatus::Installed,
+            ProgrammStatus::NotInstalled => ProgrammStatus::NotInstalled,
+            ProgrammStatus::Broken => ProgrammStatus::Broken,
+            _ => ProgrammStatus::Unknown,
+        }
+    }
+}
+
+/// Successful history actions which move a programm up in the recently used order
+fn marks_use(action: &str) -> bool {
+    matches!(action, "install" | "upgrade" | "run")
 }
 
 fn programms_buttons_style(
     status: ProgrammStatus,
 ) -> impl Fn(&Theme, button::Status) -> button::Style {
     move |_t: &Theme, s: button::Status| -> button::Style {
+        let theme = theme::current();
+        let [normal, hovered] = theme.status(status);
         button::Style {
-            background: match (status, s) {
-                (ProgrammStatus::Installed, button::Status::Hovered) => {
-                    Some(Background::Color(color!(0xA3, 0xBE, 0x8C)))
-                }
-                (ProgrammStatus::Installed, _) => Some(Background::Color(color!(0x6B, 0x82, 0x5F))),
-                (ProgrammStatus::NotInstalled, button::Status::Hovered) => {
-                    Some(Background::Color(color!(0xBF, 0x61, 0x6A)))
-                }
-                (ProgrammStatus::NotInstalled, _) => {
-                    Some(Background::Color(color!(0x8C, 0x46, 0x4F)))
-                }
+            background: match s {
+                button::Status::Hovered => Some(Background::Color(hovered)),
+                _ => Some(Background::Color(normal)),
             },
-            text_color: color!(0xE5, 0xE9, 0xF0),
+            text_color: theme.status_text.0,
             border: Border::default().rounded(4),
             shadow: Shadow::default(),
         }
@@ -636,73 +2903,282 @@ fn programms_buttons_style(
 }
 
 fn menu_buttons_style(_t: &Theme, s: button::Status) -> button::Style {
+    let theme = theme::current();
     button::Style {
         background: match s {
-            button::Status::Hovered => Some(Background::Color(color!(0x5E, 0x81, 0xAC))),
-            _ => Some(Background::Color(color!(0x4C, 0x56, 0x6A))),
+            button::Status::Hovered => Some(Background::Color(theme.button_hovered.0)),
+            _ => Some(Background::Color(theme.button.0)),
         },
-        text_color: color!(0xD8, 0xDE, 0xE9),
+        text_color: theme.button_text.0,
         border: Border::default().rounded(4),
         shadow: Shadow::default(),
     }
 }
 
 fn containers_style(_t: &Theme) -> container::Style {
+    let theme = theme::current();
     let bor = border::Border::default()
         .rounded(8)
         .width(1.5)
-        .color(color!(0x4C, 0x56, 0x6A));
+        .color(theme.panel_border.0);
     container::Style {
-        background: Some(Background::Color(color!(0x34, 0x3D, 0x4B))),
+        background: Some(Background::Color(theme.panel.0)),
         border: bor,
-        text_color: Some(color!(0xD8, 0xDE, 0xE9)),
+        text_color: Some(theme.text.0),
         shadow: Shadow::default(),
     }
 }
 
 fn markdwon_style() -> markdown::Style {
+    let theme = theme::current();
     markdown::Style {
         inline_code_highlight: Highlight {
-            background: Background::Color(color!(0x88, 0xC0, 0xD0)),
+            background: Background::Color(theme.code_background.0),
             border: Border::default(),
         },
         inline_code_padding: Padding::from(4),
-        inline_code_color: color!(0x88, 0xC0, 0xD0),
-        link_color: color!(0x81, 0xA1, 0xC1),
+        inline_code_color: theme.code.0,
+        link_color: theme.link.0,
     }
 }
 
+/// Labelled line of the settings menu
+fn setting_row<'a>(
+    label: &'a str,
+    control: impl Into<Element<'a, Message>>,
+) -> Element<'a, Message> {
+    row![text(label).size(18).width(200), control.into()]
+        .spacing(10)
+        .align_y(Alignment::Center)
+        .into()
+}
+
+/// Choices of the history result filter
+fn history_results() -> Vec<&'static str> {
+    let mut results = vec![HISTORY_ALL];
+    results.extend(Outcome::ALL.map(Outcome::label));
+    results
+}
+
 pub fn color_error() -> iced::Color {
-    color!(0xBF, 0x61, 0x6A)
+    theme::current().error.0
 }
 
 pub fn color_success() -> iced::Color {
-    color!(0xA3, 0xBE, 0x8C)
+    theme::current().success.0
+}
+
+/// Button style with a highlighted border while it has keyboard focus
+fn focused_style<'a>(
+    style: impl Fn(&Theme, button::Status) -> button::Style + 'a,
+    focused: bool,
+) -> impl Fn(&Theme, button::Status) -> button::Style + 'a {
+    move |theme, status| {
+        let style = style(theme, status);
+        if focused {
+            button::Style {
+                border: style.border.width(2).color(color_highlight()),
+                ..style
+            }
+        } else {
+            style
+        }
+    }
+}
+
+fn programms_list_id() -> scrollable::Id {
+    scrollable::Id::new("programms-list")
+}
+
+fn search_results_id() -> scrollable::Id {
+    scrollable::Id::new("search-results")
+}
+
+/// Scrolls the list so the row is in view
+fn snap_to_row(id: scrollable::Id, row: usize, len: usize) -> Task<Message> {
+    let y = row as f32 / len.saturating_sub(1).max(1) as f32;
+    scrollable::snap_to(id, scrollable::RelativeOffset { x: 0.0, y })
+}
+
+/// What choosing the palette suggestion does
+fn palette_message(suggestion: &palette::Suggestion) -> Message {
+    if suggestion.messages.is_empty() {
+        Message::PaletteComplete(suggestion.completion.clone())
+    } else {
+        Message::PaletteRun(suggestion.messages.clone())
+    }
+}
+
+/// Description snippet with matched words highlighted
+fn snippet_text(snippet: &index::Snippet) -> Element<'_, Message> {
+    let mut spans = Vec::new();
+    let mut last = 0;
+    for range in &snippet.highlights {
+        spans.push(span(&snippet.text[last..range.start]));
+        spans.push(
+            span(&snippet.text[range.clone()])
+                .color(color_highlight())
+                .underline(true),
+        );
+        last = range.end;
+    }
+    spans.push(span(&snippet.text[last..]));
+    rich_text(spans).size(13).into()
+}
+
+/// Matched characters in search results
+pub fn color_highlight() -> iced::Color {
+    theme::current().highlight.0
 }
 
 pub fn color_info() -> iced::Color {
-    color!(0xD8, 0xDE, 0xE9)
+    theme::current().info.0
 }
 
-fn run_script_in_new_window(script: &str) -> Result<Result<(), String>, String> {
-    process::Command::new("pwsh")
-        .args([
-            "-Command",
-            &format!(
-                "Start-Process pwsh -ArgumentList \'-Command\', \'{}\'",
-                script
-            ),
-        ])
-        .output()
-        .map(|output| {
-            if output.status.success() {
-                Ok(())
+/// Detects on a thread of its own, package managers and detection scripts may take long
+/// and would block the executor of the UI tasks
+async fn detect_in_background(prog: Programm) -> Detection {
+    let (sender, receiver) = oneshot::channel();
+    // Previous state stays when the thread dies
+    let previous = Detection {
+        status: prog.status,
+        installed_version: prog.installed_version.clone(),
+        available_version: prog.available_version.clone(),
+        error: Some("Detection was interrupted".to_string()),
+    };
+    thread::spawn(move || {
+        let _ = sender.send(detect(&prog));
+    });
+    receiver.await.unwrap_or(previous)
+}
+
+fn detect(prog: &Programm) -> Detection {
+    let mut available_version = None;
+    let installed_version = if let Some(package) = &prog.package {
+        let backend = package.manager.backend();
+        let installed = backend.installed_version(&package.id).ok().flatten();
+        // Asking for the latest version may hit the network, so only installed ones do it
+        if installed.is_some() {
+            available_version = backend.latest_version(&package.id).ok().flatten();
+        }
+        installed.map(Some)
+    } else if prog.archive.is_some() {
+        archive::current_version(&prog.name).map(Some)
+    } else if let Some(script) = &prog.detection {
+        match run_script_captured(script, None) {
+            Ok(output) if script.policy.completion(output.status).is_some() => {
+                Some(find_version(&String::from_utf8_lossy(&output.stdout)))
+            }
+            _ => None,
+        }
+    } else {
+        return Detection {
+            status: prog.status,
+            installed_version: prog.installed_version.clone(),
+            available_version: prog.available_version.clone(),
+            error: None,
+        };
+    };
+    let error = match installed_version {
+        Some(_) => jobs::verify(prog).err(),
+        None => None,
+    };
+    Detection {
+        status: match (&installed_version, &error) {
+            (None, _) => ProgrammStatus::NotInstalled,
+            (Some(_), Some(_)) => ProgrammStatus::Broken,
+            (Some(_), None) => ProgrammStatus::Installed,
+        },
+        installed_version: installed_version.flatten(),
+        available_version,
+        error,
+    }
+}
+
+/// Runs script with the chosen shell without a window and collects its output
+fn run_script_captured(script: &Script, dir: Option<&Path>) -> Result<process::Output, String> {
+    let (program, args) = script::shell_command(&script.run);
+    let mut command = process::Command::new(program);
+    command.args(args);
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    script::output(&mut command, &script.policy)
+}
+
+/// Runs the programm `call` on its own thread and records it in the history. The window
+/// is waited for, so the run gets its exit code, and it may stay open for hours
+async fn run_programm(name: String, call: String) -> Result<Completion, String> {
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        let started = Instant::now();
+        let mut entry = history::Entry::begin(&name, "run");
+        entry.steps = vec![call.clone()];
+        let (result, runs) = script::capture(|| run_script(&Script::from(call), None));
+        let (outcome, error) = match &result {
+            Ok(Completion::Done) => (Outcome::Ok, None),
+            Ok(Completion::NeedsReboot) => (Outcome::NeedsReboot, None),
+            Err(e) => (Outcome::Failed, Some(e.clone())),
+        };
+        entry.finish(started.elapsed(), outcome, error, &runs);
+        if let Err(e) = history::record(entry, &runs) {
+            eprintln!("Can't record history: {}", e);
+        }
+        let _ = sender.send(result);
+    });
+    receiver
+        .await
+        .unwrap_or_else(|_| Err("Run was interrupted".to_string()))
+}
+
+/// Runs the script and waits for it. Windows gives it a console window of its own,
+/// elsewhere it runs with the chosen shell like detection does
+fn run_script(script: &Script, dir: Option<&Path>) -> Result<Completion, String> {
+    let output = if cfg!(windows) {
+        run_script_in_new_window(script, dir)?
+    } else {
+        run_script_captured(script, dir)?
+    };
+    script.policy.completion(output.status).ok_or_else(|| {
+        format!(
+            "Failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    })
+}
+
+fn run_script_in_new_window(
+    script: &Script,
+    dir: Option<&Path>,
+) -> Result<process::Output, String> {
+    let (program, args) = script::shell_command(&script.run);
+    // Start-Process joins the arguments as they are, so they are quoted for the command
+    // line first, then the whole line and the program for PowerShell
+    let command_line: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            if arg.contains([' ', '\t', '"']) {
+                format!("\"{}\"", arg.replace('"', "\\\""))
             } else {
-                Err(format!(
-                    "Installation failed: {:?}",
-                    String::from_utf8(output.stderr)
-                ))
+                arg.clone()
             }
         })
-        .map_err(|e| e.to_string())
+        .collect();
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+    let mut command = process::Command::new("pwsh");
+    command.args([
+        "-NoProfile",
+        "-Command",
+        &format!(
+            "$p = Start-Process -FilePath {} -Wait -PassThru -ArgumentList {}; exit $p.ExitCode",
+            quote(&program),
+            quote(&command_line.join(" "))
+        ),
+    ]);
+    // Started window inherits the working directory
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    script::output(&mut command, &script.policy)
 }
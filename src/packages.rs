@@ -0,0 +1,1123 @@
+use crate::{
+    elevation, env,
+    script::{self, Completion, RunPolicy},
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::Path, process};
+
+/// Package managers a programm can be installed with instead of raw scripts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Manager {
+    Winget,
+    Scoop,
+    Choco,
+    Apt,
+    Dnf,
+    Pacman,
+    Brew,
+    Cargo,
+    Pipx,
+    Npm,
+    Go,
+    /// Ids are `toolchain:<name>`, `component:<name>` or `target:<triple>`
+    Rustup,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PackageSpec {
+    pub manager: Manager,
+    pub id: String,
+    /// Timeout, retries and exit codes of the manager commands
+    #[serde(flatten)]
+    pub policy: RunPolicy,
+}
+
+/// Single external command, run directly without a shell
+#[derive(Debug, Clone)]
+pub struct PackageCommand {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+pub trait PackageManager {
+    /// Command builders fail for ids the manager can't take
+    fn install(&self, id: &str) -> Result<PackageCommand, String>;
+    fn uninstall(&self, id: &str) -> Result<PackageCommand, String>;
+    fn upgrade(&self, id: &str) -> Result<PackageCommand, String>;
+    /// Restores files of the installed package in place, plain install by default.
+    /// Commands run in order, the first failing one stops the job
+    fn repair(&self, id: &str) -> Result<Vec<PackageCommand>, String> {
+        Ok(vec![self.install(id)?])
+    }
+    /// Command which lists the package when it is installed
+    fn query(&self, id: &str) -> Result<PackageCommand, String>;
+    /// Installed version from successful `query` output, `None` if package isn't there
+    fn parse_query(&self, id: &str, stdout: &str) -> Option<String>;
+
+    fn installed_version(&self, id: &str) -> Result<Option<String>, String> {
+        let output = self.query(id)?.output(&query_policy())?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        Ok(self.parse_query(id, &String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Command which prints the newest available version, `None` when manager can't tell it
+    fn latest_query(&self, _id: &str) -> Option<PackageCommand> {
+        None
+    }
+    fn parse_latest(&self, _id: &str, _stdout: &str) -> Option<String> {
+        None
+    }
+
+    fn latest_version(&self, id: &str) -> Result<Option<String>, String> {
+        let Some(query) = self.latest_query(id) else {
+            return Ok(None);
+        };
+        let output = query.output(&query_policy())?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        Ok(self.parse_latest(id, &String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+/// Version queries are killed after this long, so a hanging manager can't keep
+/// the status detecting forever
+const QUERY_TIMEOUT_SECS: u64 = 60;
+
+fn query_policy() -> RunPolicy {
+    RunPolicy {
+        timeout_secs: Some(QUERY_TIMEOUT_SECS),
+        ..RunPolicy::default()
+    }
+}
+
+impl Manager {
+    pub fn name(self) -> &'static str {
+        match self {
+            Manager::Winget => "winget",
+            Manager::Scoop => "scoop",
+            Manager::Choco => "choco",
+            Manager::Apt => "apt",
+            Manager::Dnf => "dnf",
+            Manager::Pacman => "pacman",
+            Manager::Brew => "brew",
+            Manager::Cargo => "cargo",
+            Manager::Pipx => "pipx",
+            Manager::Npm => "npm",
+            Manager::Go => "go",
+            Manager::Rustup => "rustup",
+        }
+    }
+
+    /// Whether the manager exists on this platform at all, not whether it is installed
+    pub fn is_supported(self) -> bool {
+        match self {
+            Manager::Winget | Manager::Scoop | Manager::Choco => cfg!(windows),
+            Manager::Apt | Manager::Dnf | Manager::Pacman => cfg!(target_os = "linux"),
+            Manager::Brew => cfg!(any(target_os = "macos", target_os = "linux")),
+            _ => true,
+        }
+    }
+
+    /// Manager changes the whole system, so it always runs elevated
+    pub fn is_system(self) -> bool {
+        matches!(
+            self,
+            Manager::Choco | Manager::Apt | Manager::Dnf | Manager::Pacman
+        )
+    }
+
+    pub fn from_name(name: &str) -> Option<Manager> {
+        serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+    }
+
+    pub fn backend(self) -> &'static dyn PackageManager {
+        match self {
+            Manager::Winget => &Winget,
+            Manager::Scoop => &Scoop,
+            Manager::Choco => &Choco,
+            Manager::Apt => &Apt,
+            Manager::Dnf => &Dnf,
+            Manager::Pacman => &Pacman,
+            Manager::Brew => &Brew,
+            Manager::Cargo => &Cargo,
+            Manager::Pipx => &Pipx,
+            Manager::Npm => &Npm,
+            Manager::Go => &Go,
+            Manager::Rustup => &Rustup,
+        }
+    }
+}
+
+impl fmt::Display for PackageSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.manager.name(), self.id)
+    }
+}
+
+impl PackageCommand {
+    fn new(program: &str, args: &[&str]) -> Self {
+        PackageCommand {
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    /// Script run by the chosen shell, for managers without a fitting subcommand
+    pub fn shell(script: String) -> Self {
+        let (program, args) = script::shell_command(&script);
+        PackageCommand { program, args }
+    }
+
+    /// Same command run through the elevation helper
+    pub fn elevated(self) -> Result<Self, String> {
+        let (program, args) = elevation::wrap(&self.program, &self.args)?;
+        Ok(PackageCommand { program, args })
+    }
+
+    /// Process with the variables changed by installed programms
+    fn command(&self) -> process::Command {
+        let mut command = process::Command::new(&self.program);
+        env::apply_to(&mut command);
+        command.args(&self.args);
+        command
+    }
+
+    /// Output of the command run under the policy, whatever its exit code
+    pub fn output(&self, policy: &RunPolicy) -> Result<process::Output, String> {
+        script::output(&mut self.command(), policy)
+    }
+
+    /// Runs the command under the policy and turns failing exit codes into an error with its stderr
+    pub fn run(&self, policy: &RunPolicy, dir: &Path) -> Result<Completion, String> {
+        let mut command = self.command();
+        command.current_dir(dir);
+        let output = script::output(&mut command, policy)?;
+        policy.completion(output.status).ok_or_else(|| {
+            format!(
+                "\"{}\" failed ({}): {}",
+                self,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+        })
+    }
+}
+
+impl fmt::Display for PackageCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            if arg.contains(' ') {
+                write!(f, " \"{}\"", arg)?;
+            } else {
+                write!(f, " {}", arg)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Version from the first line whose first column is the package id
+fn version_after_id(id: &str, stdout: &str) -> Option<String> {
+    stdout.lines().find_map(|line| {
+        let mut columns = line.split_whitespace();
+        (columns.next()? == id).then(|| columns.next().map(str::to_string))?
+    })
+}
+
+/// Value of the first `Key: value` line, keys may be padded with spaces
+fn field_value(key: &str, stdout: &str) -> Option<String> {
+    stdout.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        (name.trim() == key)
+            .then(|| value.trim().to_string())
+            .filter(|v| !v.is_empty())
+    })
+}
+
+struct Winget;
+struct Scoop;
+struct Choco;
+struct Apt;
+struct Dnf;
+struct Pacman;
+struct Brew;
+struct Cargo;
+struct Pipx;
+struct Npm;
+struct Go;
+struct Rustup;
+
+const WINGET_AGREEMENTS: [&str; 2] = ["--accept-package-agreements", "--accept-source-agreements"];
+
+impl PackageManager for Winget {
+    fn install(&self, id: &str) -> Result<PackageCommand, String> {
+        let mut cmd = PackageCommand::new("winget", &["install", "--id", id, "-e", "--silent"]);
+        cmd.args.extend(WINGET_AGREEMENTS.map(String::from));
+        Ok(cmd)
+    }
+    fn uninstall(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new(
+            "winget",
+            &["uninstall", "--id", id, "-e", "--silent"],
+        ))
+    }
+    fn upgrade(&self, id: &str) -> Result<PackageCommand, String> {
+        let mut cmd = PackageCommand::new("winget", &["upgrade", "--id", id, "-e", "--silent"]);
+        cmd.args.extend(WINGET_AGREEMENTS.map(String::from));
+        Ok(cmd)
+    }
+    fn repair(&self, id: &str) -> Result<Vec<PackageCommand>, String> {
+        let mut cmd = PackageCommand::new("winget", &["repair", "--id", id, "-e", "--silent"]);
+        cmd.args.extend(WINGET_AGREEMENTS.map(String::from));
+        Ok(vec![cmd])
+    }
+    fn query(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new(
+            "winget",
+            &["list", "--id", id, "-e", "--accept-source-agreements"],
+        ))
+    }
+    fn parse_query(&self, id: &str, stdout: &str) -> Option<String> {
+        // Name column may contain spaces, so look for the id column and take the next one
+        stdout.lines().find_map(|line| {
+            let mut columns = line.split_whitespace().skip_while(|c| *c != id);
+            columns.next()?;
+            columns.next().map(str::to_string)
+        })
+    }
+    fn latest_query(&self, id: &str) -> Option<PackageCommand> {
+        Some(PackageCommand::new(
+            "winget",
+            &["show", "--id", id, "-e", "--accept-source-agreements"],
+        ))
+    }
+    fn parse_latest(&self, _id: &str, stdout: &str) -> Option<String> {
+        field_value("Version", stdout)
+    }
+}
+
+impl PackageManager for Scoop {
+    fn install(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("scoop", &["install", id]))
+    }
+    fn uninstall(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("scoop", &["uninstall", id]))
+    }
+    fn upgrade(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("scoop", &["update", id]))
+    }
+    fn repair(&self, id: &str) -> Result<Vec<PackageCommand>, String> {
+        // Scoop refuses to install an installed app again
+        Ok(vec![self.uninstall(id)?, self.install(id)?])
+    }
+    fn query(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("scoop", &["list", id]))
+    }
+    fn parse_query(&self, id: &str, stdout: &str) -> Option<String> {
+        version_after_id(id, stdout)
+    }
+    fn latest_query(&self, id: &str) -> Option<PackageCommand> {
+        Some(PackageCommand::new("scoop", &["info", id]))
+    }
+    fn parse_latest(&self, _id: &str, stdout: &str) -> Option<String> {
+        field_value("Version", stdout)
+    }
+}
+
+impl PackageManager for Choco {
+    fn install(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("choco", &["install", id, "-y"]))
+    }
+    fn uninstall(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("choco", &["uninstall", id, "-y"]))
+    }
+    fn upgrade(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("choco", &["upgrade", id, "-y"]))
+    }
+    fn repair(&self, id: &str) -> Result<Vec<PackageCommand>, String> {
+        Ok(vec![PackageCommand::new(
+            "choco",
+            &["install", id, "-y", "--force"],
+        )])
+    }
+    fn query(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new(
+            "choco",
+            &["list", "--exact", id, "--limit-output"],
+        ))
+    }
+    fn parse_query(&self, id: &str, stdout: &str) -> Option<String> {
+        stdout.lines().find_map(|line| {
+            let (name, version) = line.trim().split_once('|')?;
+            name.eq_ignore_ascii_case(id).then(|| version.to_string())
+        })
+    }
+    fn latest_query(&self, id: &str) -> Option<PackageCommand> {
+        Some(PackageCommand::new(
+            "choco",
+            &["search", "--exact", id, "--limit-output"],
+        ))
+    }
+    fn parse_latest(&self, id: &str, stdout: &str) -> Option<String> {
+        // Same `id|version` lines as the local list
+        self.parse_query(id, stdout)
+    }
+}
+
+impl PackageManager for Apt {
+    fn install(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("apt-get", &["install", "-y", id]))
+    }
+    fn uninstall(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("apt-get", &["remove", "-y", id]))
+    }
+    fn upgrade(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new(
+            "apt-get",
+            &["install", "--only-upgrade", "-y", id],
+        ))
+    }
+    fn repair(&self, id: &str) -> Result<Vec<PackageCommand>, String> {
+        Ok(vec![PackageCommand::new(
+            "apt-get",
+            &["install", "--reinstall", "-y", id],
+        )])
+    }
+    fn query(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new(
+            "dpkg-query",
+            &["-W", "-f=${db:Status-Status} ${Version}", id],
+        ))
+    }
+    fn parse_query(&self, _id: &str, stdout: &str) -> Option<String> {
+        stdout.trim().strip_prefix("installed ").map(str::to_string)
+    }
+    fn latest_query(&self, id: &str) -> Option<PackageCommand> {
+        Some(PackageCommand::new("apt-cache", &["policy", id]))
+    }
+    fn parse_latest(&self, _id: &str, stdout: &str) -> Option<String> {
+        field_value("Candidate", stdout).filter(|v| v != "(none)")
+    }
+}
+
+impl PackageManager for Dnf {
+    fn install(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("dnf", &["install", "-y", id]))
+    }
+    fn uninstall(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("dnf", &["remove", "-y", id]))
+    }
+    fn upgrade(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("dnf", &["upgrade", "-y", id]))
+    }
+    fn repair(&self, id: &str) -> Result<Vec<PackageCommand>, String> {
+        Ok(vec![PackageCommand::new("dnf", &["reinstall", "-y", id])])
+    }
+    fn query(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new(
+            "rpm",
+            &["-q", "--qf", "%{VERSION}-%{RELEASE}", id],
+        ))
+    }
+    fn parse_query(&self, _id: &str, stdout: &str) -> Option<String> {
+        Some(stdout.trim().to_string()).filter(|v| !v.is_empty())
+    }
+    fn latest_query(&self, id: &str) -> Option<PackageCommand> {
+        Some(PackageCommand::new(
+            "dnf",
+            &[
+                "repoquery",
+                "--quiet",
+                "--latest-limit",
+                "1",
+                "--qf",
+                "%{version}-%{release}\n",
+                id,
+            ],
+        ))
+    }
+    fn parse_latest(&self, _id: &str, stdout: &str) -> Option<String> {
+        stdout
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(str::to_string)
+    }
+}
+
+impl PackageManager for Pacman {
+    fn install(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("pacman", &["-S", "--noconfirm", id]))
+    }
+    fn uninstall(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("pacman", &["-R", "--noconfirm", id]))
+    }
+    fn upgrade(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("pacman", &["-S", "--noconfirm", id]))
+    }
+    fn query(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("pacman", &["-Q", id]))
+    }
+    fn parse_query(&self, id: &str, stdout: &str) -> Option<String> {
+        version_after_id(id, stdout)
+    }
+    fn latest_query(&self, id: &str) -> Option<PackageCommand> {
+        Some(PackageCommand::new("pacman", &["-Si", id]))
+    }
+    fn parse_latest(&self, _id: &str, stdout: &str) -> Option<String> {
+        field_value("Version", stdout)
+    }
+}
+
+impl PackageManager for Brew {
+    fn install(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("brew", &["install", id]))
+    }
+    fn uninstall(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("brew", &["uninstall", id]))
+    }
+    fn upgrade(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("brew", &["upgrade", id]))
+    }
+    fn repair(&self, id: &str) -> Result<Vec<PackageCommand>, String> {
+        Ok(vec![PackageCommand::new("brew", &["reinstall", id])])
+    }
+    fn query(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("brew", &["list", "--versions", id]))
+    }
+    fn parse_query(&self, id: &str, stdout: &str) -> Option<String> {
+        // Several versions may be kept side by side, the last one is the newest
+        stdout.lines().find_map(|line| {
+            let mut columns = line.split_whitespace();
+            (columns.next()? == id).then(|| columns.last().map(str::to_string))?
+        })
+    }
+    fn latest_query(&self, id: &str) -> Option<PackageCommand> {
+        Some(PackageCommand::new("brew", &["info", "--json=v2", id]))
+    }
+    fn parse_latest(&self, _id: &str, stdout: &str) -> Option<String> {
+        let info: serde_json::Value = serde_json::from_str(stdout).ok()?;
+        info["formulae"][0]["versions"]["stable"]
+            .as_str()
+            .map(str::to_string)
+    }
+}
+
+impl PackageManager for Cargo {
+    fn install(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("cargo", &["install", id]))
+    }
+    fn uninstall(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("cargo", &["uninstall", id]))
+    }
+    fn upgrade(&self, id: &str) -> Result<PackageCommand, String> {
+        // `cargo install` replaces the crate when a newer version is published
+        Ok(PackageCommand::new("cargo", &["install", id]))
+    }
+    fn repair(&self, id: &str) -> Result<Vec<PackageCommand>, String> {
+        Ok(vec![PackageCommand::new(
+            "cargo",
+            &["install", "--force", id],
+        )])
+    }
+    fn query(&self, _id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("cargo", &["install", "--list"]))
+    }
+    fn parse_query(&self, id: &str, stdout: &str) -> Option<String> {
+        // Lines look like `ripgrep v14.1.0:` followed by indented binaries
+        version_after_id(id, stdout)
+            .map(|v| v.trim_start_matches('v').trim_end_matches(':').to_string())
+    }
+    fn latest_query(&self, id: &str) -> Option<PackageCommand> {
+        Some(PackageCommand::new(
+            "cargo",
+            &["search", id, "--limit", "1"],
+        ))
+    }
+    fn parse_latest(&self, id: &str, stdout: &str) -> Option<String> {
+        // `ripgrep = "14.1.0"    # description`
+        stdout.lines().find_map(|line| {
+            let rest = line.strip_prefix(id)?.trim_start().strip_prefix('=')?;
+            rest.trim_start()
+                .strip_prefix('"')?
+                .split('"')
+                .next()
+                .map(str::to_string)
+        })
+    }
+}
+
+impl PackageManager for Pipx {
+    fn install(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("pipx", &["install", id]))
+    }
+    fn uninstall(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("pipx", &["uninstall", id]))
+    }
+    fn upgrade(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("pipx", &["upgrade", id]))
+    }
+    fn repair(&self, id: &str) -> Result<Vec<PackageCommand>, String> {
+        Ok(vec![PackageCommand::new("pipx", &["reinstall", id])])
+    }
+    fn query(&self, _id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("pipx", &["list", "--json"]))
+    }
+    fn parse_query(&self, id: &str, stdout: &str) -> Option<String> {
+        let list: serde_json::Value = serde_json::from_str(stdout).ok()?;
+        list["venvs"][id]["metadata"]["main_package"]["package_version"]
+            .as_str()
+            .map(str::to_string)
+    }
+}
+
+impl PackageManager for Npm {
+    fn install(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("npm", &["install", "-g", id]))
+    }
+    fn uninstall(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new("npm", &["uninstall", "-g", id]))
+    }
+    fn upgrade(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new(
+            "npm",
+            &["install", "-g", &format!("{}@latest", id)],
+        ))
+    }
+    fn query(&self, _id: &str) -> Result<PackageCommand, String> {
+        Ok(PackageCommand::new(
+            "npm",
+            &["ls", "-g", "--depth=0", "--json"],
+        ))
+    }
+    fn parse_query(&self, id: &str, stdout: &str) -> Option<String> {
+        let list: serde_json::Value = serde_json::from_str(stdout).ok()?;
+        list["dependencies"][id]["version"]
+            .as_str()
+            .map(str::to_string)
+    }
+    fn latest_query(&self, id: &str) -> Option<PackageCommand> {
+        Some(PackageCommand::new("npm", &["view", id, "version"]))
+    }
+    fn parse_latest(&self, _id: &str, stdout: &str) -> Option<String> {
+        Some(stdout.trim().to_string()).filter(|v| !v.is_empty())
+    }
+}
+
+impl Go {
+    /// Name of the binary `go install` produces for the module path, it goes into shell
+    /// scripts, so only characters Go allows in module paths are accepted
+    fn binary_name(id: &str) -> Result<String, String> {
+        let path = id.split('@').next().unwrap_or(id);
+        let mut segments = path.rsplit('/');
+        let last = segments.next().unwrap_or(path);
+        // Major version suffix like `.../v2` isn't a part of binary name
+        let is_major_version = last.len() > 1
+            && last.starts_with('v')
+            && last[1..].chars().all(|c| c.is_ascii_digit());
+        let name = match segments.next() {
+            Some(parent) if is_major_version => parent,
+            _ => last,
+        };
+        let valid = name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '~'));
+        if name.is_empty() || name.starts_with('.') || !valid {
+            return Err(format!("\"{}\" isn't a Go module path", id));
+        }
+        Ok(if cfg!(windows) {
+            format!("{}.exe", name)
+        } else {
+            name.to_string()
+        })
+    }
+
+    /// Shell snippet which puts the directory `go install` writes binaries to into `bin` variable
+    fn bin_dir_script() -> &'static str {
+        if cfg!(windows) {
+            "$bin = go env GOBIN; if (-not $bin) { $bin = Join-Path (go env GOPATH) 'bin' }"
+        } else {
+            "bin=$(go env GOBIN); [ -n \"$bin\" ] || bin=\"$(go env GOPATH)/bin\""
+        }
+    }
+}
+
+impl PackageManager for Go {
+    fn install(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(if id.contains('@') {
+            PackageCommand::new("go", &["install", id])
+        } else {
+            PackageCommand::new("go", &["install", &format!("{}@latest", id)])
+        })
+    }
+    fn uninstall(&self, id: &str) -> Result<PackageCommand, String> {
+        // Go has no uninstall, the binary is simply removed
+        let name = Go::binary_name(id)?;
+        Ok(PackageCommand::shell(if cfg!(windows) {
+            format!(
+                "{}; Remove-Item (Join-Path $bin '{}')",
+                Go::bin_dir_script(),
+                name
+            )
+        } else {
+            format!("{}; rm -f \"$bin/{}\"", Go::bin_dir_script(), name)
+        }))
+    }
+    fn upgrade(&self, id: &str) -> Result<PackageCommand, String> {
+        let module = id.split('@').next().unwrap_or(id);
+        Ok(PackageCommand::new(
+            "go",
+            &["install", &format!("{}@latest", module)],
+        ))
+    }
+    fn query(&self, id: &str) -> Result<PackageCommand, String> {
+        let name = Go::binary_name(id)?;
+        Ok(PackageCommand::shell(if cfg!(windows) {
+            format!(
+                "{}; go version -m (Join-Path $bin '{}')",
+                Go::bin_dir_script(),
+                name
+            )
+        } else {
+            format!("{}; go version -m \"$bin/{}\"", Go::bin_dir_script(), name)
+        }))
+    }
+    fn parse_query(&self, _id: &str, stdout: &str) -> Option<String> {
+        // `go version -m` prints build info, module line is `\tmod\t<path>\t<version>\t<sum>`
+        stdout.lines().find_map(|line| {
+            let mut columns = line.split_whitespace();
+            (columns.next()? == "mod").then(|| {
+                columns
+                    .nth(1)
+                    .map(|v| v.trim_start_matches('v').to_string())
+            })?
+        })
+    }
+    fn latest_query(&self, id: &str) -> Option<PackageCommand> {
+        let module = id.split('@').next().unwrap_or(id);
+        Some(PackageCommand::new(
+            "go",
+            &["list", "-m", "-json", &format!("{}@latest", module)],
+        ))
+    }
+    fn parse_latest(&self, _id: &str, stdout: &str) -> Option<String> {
+        let module: serde_json::Value = serde_json::from_str(stdout).ok()?;
+        module["Version"]
+            .as_str()
+            .map(|v| v.trim_start_matches('v').to_string())
+    }
+}
+
+enum RustupItem<'a> {
+    Toolchain(&'a str),
+    Component(&'a str),
+    Target(&'a str),
+}
+
+impl Rustup {
+    /// Plain id is a toolchain name
+    fn item(id: &str) -> Result<RustupItem<'_>, String> {
+        match id.split_once(':') {
+            Some(("toolchain", name)) => Ok(RustupItem::Toolchain(name)),
+            Some(("component", name)) => Ok(RustupItem::Component(name)),
+            Some(("target", name)) => Ok(RustupItem::Target(name)),
+            Some((kind, _)) => Err(format!(
+                "Unknown rustup item \"{}\" in \"{}\", expected toolchain, component or target",
+                kind, id
+            )),
+            None => Ok(RustupItem::Toolchain(id)),
+        }
+    }
+}
+
+impl PackageManager for Rustup {
+    fn install(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(match Rustup::item(id)? {
+            RustupItem::Toolchain(name) => {
+                PackageCommand::new("rustup", &["toolchain", "install", name])
+            }
+            RustupItem::Component(name) => {
+                PackageCommand::new("rustup", &["component", "add", name])
+            }
+            RustupItem::Target(name) => PackageCommand::new("rustup", &["target", "add", name]),
+        })
+    }
+    fn uninstall(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(match Rustup::item(id)? {
+            RustupItem::Toolchain(name) => {
+                PackageCommand::new("rustup", &["toolchain", "uninstall", name])
+            }
+            RustupItem::Component(name) => {
+                PackageCommand::new("rustup", &["component", "remove", name])
+            }
+            RustupItem::Target(name) => PackageCommand::new("rustup", &["target", "remove", name]),
+        })
+    }
+    fn upgrade(&self, id: &str) -> Result<PackageCommand, String> {
+        // Components and targets are updated together with the active toolchain
+        Ok(match Rustup::item(id)? {
+            RustupItem::Toolchain(name) => PackageCommand::new("rustup", &["update", name]),
+            _ => PackageCommand::new("rustup", &["update"]),
+        })
+    }
+    fn repair(&self, id: &str) -> Result<Vec<PackageCommand>, String> {
+        Ok(vec![match Rustup::item(id)? {
+            RustupItem::Toolchain(name) => {
+                PackageCommand::new("rustup", &["toolchain", "install", "--force", name])
+            }
+            _ => self.install(id)?,
+        }])
+    }
+    fn query(&self, id: &str) -> Result<PackageCommand, String> {
+        Ok(match Rustup::item(id)? {
+            RustupItem::Toolchain(name) => {
+                PackageCommand::new("rustup", &["run", name, "rustc", "--version"])
+            }
+            RustupItem::Component(_) => {
+                PackageCommand::new("rustup", &["component", "list", "--installed"])
+            }
+            RustupItem::Target(_) => {
+                PackageCommand::new("rustup", &["target", "list", "--installed"])
+            }
+        })
+    }
+    fn parse_query(&self, id: &str, stdout: &str) -> Option<String> {
+        match Rustup::item(id).ok()? {
+            // `rustc 1.80.0 (051478957 2024-07-21)`
+            RustupItem::Toolchain(_) => stdout.split_whitespace().nth(1).map(str::to_string),
+            // Most components are listed with the host triple appended, e.g.
+            // `clippy-x86_64-pc-windows-msvc`, the triple is the one `rustc` is listed with
+            RustupItem::Component(name) => {
+                let host = stdout
+                    .lines()
+                    .find_map(|line| line.trim().strip_prefix("rustc-"));
+                stdout
+                    .lines()
+                    .map(str::trim)
+                    .find(|line| {
+                        *line == name
+                            || host.is_some_and(|host| *line == format!("{}-{}", name, host))
+                    })
+                    .map(str::to_string)
+            }
+            RustupItem::Target(name) => stdout
+                .lines()
+                .map(str::trim)
+                .find(|line| *line == name)
+                .map(str::to_string),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::compare_versions;
+    use std::{
+        cmp::Ordering, env, fs, os::unix::fs::PermissionsExt, path::PathBuf, sync::OnceLock,
+    };
+
+    /// Shell scripts answering like the real managers, in a directory put first on PATH.
+    /// Every call is also logged to `calls` in the working directory when that file exists
+    const FAKE_MANAGERS: &[(&str, &str)] = &[
+        (
+            "winget",
+            r#"case "$1" in
+list) printf 'Name       Id         Version Source\n---\nVisual Studio Code Microsoft.VisualStudioCode 1.91.1 winget\n' ;;
+show) printf 'Found Visual Studio Code [Microsoft.VisualStudioCode]\nVersion: 1.92.0\nPublisher: Microsoft\n' ;;
+esac"#,
+        ),
+        ("apt-get", "true"),
+        (
+            "dpkg-query",
+            r#"case "$3" in
+clang) printf 'installed 1:14.0-55.7' ;;
+*) echo "dpkg-query: no packages found matching $3" >&2; exit 1 ;;
+esac"#,
+        ),
+        (
+            "apt-cache",
+            r#"printf '%s:\n  Installed: 1:14.0-55.7\n  Candidate: 1:15.0-56\n' "$2""#,
+        ),
+        (
+            "brew",
+            r#"case "$1" in
+list) [ "$3" = jq ] && echo 'jq 1.6 1.7.1' ;;
+info) echo '{"formulae":[{"name":"jq","versions":{"stable":"1.7.1","head":"HEAD"}}]}' ;;
+esac"#,
+        ),
+        (
+            "rustup",
+            r#"case "$1" in
+run) [ "$2" = stable ] && echo 'rustc 1.80.0 (051478957 2024-07-21)' || exit 1 ;;
+component) printf 'cargo-x86_64-unknown-linux-gnu\nclippy-x86_64-unknown-linux-gnu\nrust-src\nrustc-x86_64-unknown-linux-gnu\n' ;;
+target) echo 'wasm32-unknown-unknown' ;;
+esac"#,
+        ),
+    ];
+
+    /// Puts the fake managers first on PATH of this process, which commands inherit.
+    /// Written once, rewriting a stub while another test runs it fails with "text file busy"
+    fn fake_managers() {
+        static DONE: OnceLock<()> = OnceLock::new();
+        DONE.get_or_init(|| {
+            let dir = env::temp_dir().join(format!("wtb-fake-managers-{}", process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            for (name, body) in FAKE_MANAGERS {
+                let path = dir.join(name);
+                let log = "if [ -f calls ]; then echo \"$(basename \"$0\") $*\" >> calls; fi";
+                fs::write(&path, format!("#!/bin/sh\n{}\n{}\n", log, body)).unwrap();
+                fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            }
+            let system = env::var_os("PATH").unwrap_or_default();
+            let mut paths = vec![dir];
+            paths.extend(env::split_paths(&system).collect::<Vec<PathBuf>>());
+            env::set_var("PATH", env::join_paths(paths).unwrap());
+        });
+    }
+
+    fn installed(manager: Manager, id: &str) -> Option<String> {
+        fake_managers();
+        manager.backend().installed_version(id).unwrap()
+    }
+
+    fn latest(manager: Manager, id: &str) -> Option<String> {
+        fake_managers();
+        manager.backend().latest_version(id).unwrap()
+    }
+
+    /// Command lines the manager gets for install, upgrade and uninstall, in that order
+    fn changes(manager: Manager, id: &str) -> Vec<String> {
+        fake_managers();
+        let dir =
+            env::temp_dir().join(format!("wtb-packages-{}-{}", manager.name(), process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("calls"), "").unwrap();
+        let backend = manager.backend();
+        for command in [
+            backend.install(id).unwrap(),
+            backend.upgrade(id).unwrap(),
+            backend.uninstall(id).unwrap(),
+        ] {
+            command.run(&RunPolicy::default(), &dir).unwrap();
+        }
+        let calls = fs::read_to_string(dir.join("calls")).unwrap();
+        calls.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn winget_versions() {
+        let id = "Microsoft.VisualStudioCode";
+        assert_eq!(installed(Manager::Winget, id).as_deref(), Some("1.91.1"));
+        assert_eq!(latest(Manager::Winget, id).as_deref(), Some("1.92.0"));
+    }
+
+    #[test]
+    fn apt_versions() {
+        assert_eq!(
+            installed(Manager::Apt, "clang").as_deref(),
+            Some("1:14.0-55.7")
+        );
+        assert_eq!(installed(Manager::Apt, "llvm"), None);
+        assert_eq!(latest(Manager::Apt, "clang").as_deref(), Some("1:15.0-56"));
+    }
+
+    #[test]
+    fn brew_versions() {
+        assert_eq!(installed(Manager::Brew, "jq").as_deref(), Some("1.7.1"));
+        assert_eq!(installed(Manager::Brew, "yq"), None);
+        assert_eq!(latest(Manager::Brew, "jq").as_deref(), Some("1.7.1"));
+    }
+
+    #[test]
+    fn rustup_versions() {
+        assert_eq!(
+            installed(Manager::Rustup, "toolchain:stable").as_deref(),
+            Some("1.80.0")
+        );
+        assert_eq!(installed(Manager::Rustup, "toolchain:nightly"), None);
+        assert!(installed(Manager::Rustup, "component:clippy").is_some());
+        assert!(installed(Manager::Rustup, "component:rust-src").is_some());
+        assert_eq!(installed(Manager::Rustup, "component:rust"), None);
+        assert_eq!(installed(Manager::Rustup, "component:rust-analyzer"), None);
+        assert!(installed(Manager::Rustup, "target:wasm32-unknown-unknown").is_some());
+        assert_eq!(
+            installed(Manager::Rustup, "target:aarch64-apple-darwin"),
+            None
+        );
+        assert_eq!(latest(Manager::Rustup, "toolchain:stable"), None);
+    }
+
+    #[test]
+    fn winget_commands() {
+        let agreements = "--accept-package-agreements --accept-source-agreements";
+        assert_eq!(
+            changes(Manager::Winget, "Microsoft.VisualStudioCode"),
+            [
+                format!(
+                    "winget install --id Microsoft.VisualStudioCode -e --silent {}",
+                    agreements
+                ),
+                format!(
+                    "winget upgrade --id Microsoft.VisualStudioCode -e --silent {}",
+                    agreements
+                ),
+                "winget uninstall --id Microsoft.VisualStudioCode -e --silent".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn apt_commands() {
+        assert_eq!(
+            changes(Manager::Apt, "clang"),
+            [
+                "apt-get install -y clang",
+                "apt-get install --only-upgrade -y clang",
+                "apt-get remove -y clang",
+            ]
+        );
+    }
+
+    #[test]
+    fn brew_commands() {
+        assert_eq!(
+            changes(Manager::Brew, "jq"),
+            ["brew install jq", "brew upgrade jq", "brew uninstall jq"]
+        );
+    }
+
+    #[test]
+    fn rustup_commands() {
+        assert_eq!(
+            changes(Manager::Rustup, "component:clippy"),
+            [
+                "rustup component add clippy",
+                "rustup update",
+                "rustup component remove clippy",
+            ]
+        );
+    }
+
+    #[test]
+    fn cargo_output_is_parsed() {
+        let cargo = Manager::Cargo.backend();
+        let list = "cargo-edit v0.12.3:\n    cargo-add\n    cargo-rm\nripgrep v14.1.0:\n    rg\n";
+        assert_eq!(
+            cargo.parse_query("ripgrep", list).as_deref(),
+            Some("14.1.0")
+        );
+        // Binary names are indented, so they don't count as installed crates
+        assert_eq!(cargo.parse_query("rg", list), None);
+        assert_eq!(cargo.parse_query("fd-find", list), None);
+        assert_eq!(cargo.parse_query("ripgrep", ""), None);
+        assert_eq!(cargo.parse_query("ripgrep", "ripgrep\n"), None);
+
+        let search = "ripgrep = \"14.1.0\"    # ripgrep is a line-oriented search tool\n\
+                      ... and 321 crates more (use --limit N to see more)\n";
+        assert_eq!(
+            cargo.parse_latest("ripgrep", search).as_deref(),
+            Some("14.1.0")
+        );
+        assert_eq!(
+            cargo.parse_latest("ripgrep", "ripgrep_all = \"0.10.6\"    # rga\n"),
+            None
+        );
+        assert_eq!(cargo.parse_latest("ripgrep", "ripgrep = 14.1.0\n"), None);
+        assert_eq!(cargo.parse_latest("ripgrep", ""), None);
+    }
+
+    #[test]
+    fn pipx_output_is_parsed() {
+        let pipx = Manager::Pipx.backend();
+        let list = r#"{"pipx_spec_version": "0.1", "venvs": {"black": {"metadata": {
+            "main_package": {"package": "black", "package_version": "24.4.2"}}}}}"#;
+        assert_eq!(pipx.parse_query("black", list).as_deref(), Some("24.4.2"));
+        assert_eq!(pipx.parse_query("ruff", list), None);
+        assert_eq!(
+            pipx.parse_query("black", r#"{"pipx_spec_version": "0.1", "venvs": {}}"#),
+            None
+        );
+        assert_eq!(
+            pipx.parse_query("black", "nothing has been installed with pipx"),
+            None
+        );
+        assert_eq!(pipx.parse_query("black", r#"{"venvs": {"black": "#), None);
+        assert!(pipx.latest_query("black").is_none());
+    }
+
+    #[test]
+    fn npm_output_is_parsed() {
+        let npm = Manager::Npm.backend();
+        let list = r#"{"name": "lib", "dependencies": {
+            "npm": {"version": "10.8.1", "overridden": false},
+            "typescript": {"version": "5.5.3", "overridden": false}}}"#;
+        assert_eq!(
+            npm.parse_query("typescript", list).as_deref(),
+            Some("5.5.3")
+        );
+        assert_eq!(npm.parse_query("prettier", list), None);
+        assert_eq!(npm.parse_query("typescript", r#"{"name": "lib"}"#), None);
+        assert_eq!(
+            npm.parse_query("typescript", "npm ERR! code ELSPROBLEMS"),
+            None
+        );
+        assert_eq!(
+            npm.parse_latest("typescript", "5.5.4\n").as_deref(),
+            Some("5.5.4")
+        );
+        assert_eq!(npm.parse_latest("typescript", "\n"), None);
+    }
+
+    #[test]
+    fn go_output_is_parsed() {
+        let go = Manager::Go.backend();
+        let id = "golang.org/x/tools/gopls";
+        let info = "/home/user/go/bin/gopls: go1.22.5\n\
+                    \tpath\tgolang.org/x/tools/gopls\n\
+                    \tmod\tgolang.org/x/tools/gopls\tv0.16.1\th1:abc=\n\
+                    \tdep\tgolang.org/x/mod\tv0.19.0\th1:def=\n";
+        assert_eq!(go.parse_query(id, info).as_deref(), Some("0.16.1"));
+        assert_eq!(
+            go.parse_query(
+                id,
+                "go: open /home/user/go/bin/gopls: no such file or directory\n"
+            ),
+            None
+        );
+        assert_eq!(go.parse_query(id, "\tmod\n"), None);
+
+        let module = r#"{"Path": "golang.org/x/tools/gopls", "Version": "v0.16.2",
+            "Time": "2024-09-05T18:32:34Z"}"#;
+        assert_eq!(go.parse_latest(id, module).as_deref(), Some("0.16.2"));
+        assert_eq!(
+            go.parse_latest(id, r#"{"Path": "golang.org/x/tools/gopls"}"#),
+            None
+        );
+        assert_eq!(go.parse_latest(id, "go: module not found"), None);
+    }
+
+    #[test]
+    fn bad_ids_are_rejected() {
+        let rustup = Manager::Rustup.backend();
+        assert!(rustup.install("componnt:clippy").is_err());
+        assert!(rustup.query("toolchain:stable").is_ok());
+        let go = Manager::Go.backend();
+        assert!(go.uninstall("example.com/tool\"; rm -rf ~; \"").is_err());
+        assert!(go.uninstall("golang.org/x/tools/gopls@v0.16.1").is_ok());
+        assert!(go.query("github.com/user/tool/v2").is_ok());
+    }
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert_eq!(compare_versions("1.91.1", "1.92.0"), Ordering::Less);
+        assert_eq!(compare_versions("0.12.0", "0.9.1"), Ordering::Greater);
+        assert_eq!(compare_versions("v1.7.1", "1.7.1"), Ordering::Equal);
+        assert_eq!(compare_versions("1.7", "1.7.1"), Ordering::Less);
+    }
+}
@@ -0,0 +1,387 @@
+use crate::{
+    index::DocsIndex, programm_action, search, templates::TEMPLATES, theme::AppTheme,
+    ControlMenuVariations, Message, Programm, ProgrammManipulation,
+};
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// What a command takes after its name
+#[derive(Clone, Copy, PartialEq)]
+enum Argument {
+    None,
+    Programm,
+    Config,
+    Menu,
+    Template,
+    Theme,
+}
+
+struct Command {
+    name: &'static str,
+    argument: Argument,
+    description: &'static str,
+    /// Messages doing the command with the chosen argument
+    messages: fn(&str, &Context) -> Vec<Message>,
+}
+
+/// State commands depend on
+pub struct Context<'a> {
+    pub programms: &'a BTreeMap<String, Programm>,
+    pub docs: &'a DocsIndex,
+    pub dry_run: bool,
+    pub keep_artifacts: bool,
+    pub themes: &'a [AppTheme],
+}
+
+/// Line of the palette. Chosen one sends its messages, or completes the input when
+/// the command still needs an argument
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub label: String,
+    pub description: &'static str,
+    pub completion: String,
+    pub messages: Vec<Message>,
+    score: i64,
+}
+
+const MENUS: [(&str, ControlMenuVariations); 6] = [
+    ("programms", ControlMenuVariations::ProgrammsMenu),
+    ("help", ControlMenuVariations::HelpMenu),
+    ("configs", ControlMenuVariations::ConfigsMenu),
+    ("updates", ControlMenuVariations::UpdatesMenu),
+    ("history", ControlMenuVariations::HistoryMenu),
+    ("settings", ControlMenuVariations::SettingsMenu),
+];
+
+/// Nothing when the programm status doesn't allow the manipulation, the same as its button
+fn manipulate(name: &str, context: &Context, manipulation: ProgrammManipulation) -> Vec<Message> {
+    context
+        .programms
+        .get(name)
+        .and_then(|prog| programm_action(prog, manipulation))
+        .map(|action| vec![Message::SelectProgrammFromList(name.to_string()), action])
+        .unwrap_or_default()
+}
+
+const COMMANDS: &[Command] = &[
+    Command {
+        name: "install",
+        argument: Argument::Programm,
+        description: "Install a programm",
+        messages: |name, context| manipulate(name, context, ProgrammManipulation::Install),
+    },
+    Command {
+        name: "uninstall",
+        argument: Argument::Programm,
+        description: "Uninstall a programm",
+        messages: |name, context| manipulate(name, context, ProgrammManipulation::Uninstall),
+    },
+    Command {
+        name: "upgrade",
+        argument: Argument::Programm,
+        description: "Upgrade a programm",
+        messages: |name, context| manipulate(name, context, ProgrammManipulation::Upgrade),
+    },
+    Command {
+        name: "reinstall",
+        argument: Argument::Programm,
+        description: "Uninstall and install a programm again",
+        messages: |name, context| manipulate(name, context, ProgrammManipulation::Reinstall),
+    },
+    Command {
+        name: "repair",
+        argument: Argument::Programm,
+        description: "Install a programm over its installation",
+        messages: |name, context| manipulate(name, context, ProgrammManipulation::Repair),
+    },
+    Command {
+        name: "run",
+        argument: Argument::Programm,
+        description: "Run a programm",
+        messages: |name, _| {
+            vec![
+                Message::SelectProgrammFromList(name.to_string()),
+                Message::RunProgrammDefault,
+            ]
+        },
+    },
+    Command {
+        name: "docs",
+        argument: Argument::Programm,
+        description: "Open documentation of a programm",
+        messages: |name, _| {
+            vec![
+                Message::SelectProgrammFromList(name.to_string()),
+                Message::OpenDocsOnline,
+            ]
+        },
+    },
+    Command {
+        name: "folder",
+        argument: Argument::Programm,
+        description: "Open folder of a programm",
+        messages: |name, _| {
+            vec![
+                Message::SelectProgrammFromList(name.to_string()),
+                Message::OpenContainingFolder,
+            ]
+        },
+    },
+    Command {
+        name: "edit",
+        argument: Argument::Programm,
+        description: "Edit a programm in the catalog",
+        messages: |name, _| vec![Message::OpenEditor(Some(name.to_string()))],
+    },
+    Command {
+        name: "new programm",
+        argument: Argument::None,
+        description: "Add a programm to the catalog",
+        messages: |_, _| vec![Message::OpenEditor(None)],
+    },
+    Command {
+        name: "switch config",
+        argument: Argument::Config,
+        description: "Load a config from the current directory",
+        messages: |path, _| vec![Message::LoadConfig(PathBuf::from(path))],
+    },
+    Command {
+        name: "new config",
+        argument: Argument::Template,
+        description: "Create a config from a template",
+        messages: |id, _| {
+            TEMPLATES
+                .iter()
+                .find(|t| t.id == id)
+                .map(|t| Message::CreateConfigFromTemplate(t.id))
+                .into_iter()
+                .collect()
+        },
+    },
+    Command {
+        name: "open",
+        argument: Argument::Menu,
+        description: "Open a menu",
+        messages: |menu, _| {
+            MENUS
+                .iter()
+                .find(|(name, _)| *name == menu)
+                .map(|(_, menu)| Message::ControlMenuBtn(*menu))
+                .into_iter()
+                .collect()
+        },
+    },
+    Command {
+        name: "theme",
+        argument: Argument::Theme,
+        description: "Switch the color theme",
+        messages: |name, _| vec![Message::SelectTheme(name.to_string())],
+    },
+    Command {
+        name: "check updates",
+        argument: Argument::None,
+        description: "Ask package managers for new versions",
+        messages: |_, _| vec![Message::CheckUpdates],
+    },
+    Command {
+        name: "upgrade all",
+        argument: Argument::None,
+        description: "Upgrade every outdated programm",
+        messages: |_, _| vec![Message::UpgradeAll],
+    },
+    Command {
+        name: "dry run",
+        argument: Argument::None,
+        description: "Turn dry run on or off",
+        messages: |_, context| vec![Message::ToggleDryRun(!context.dry_run)],
+    },
+    Command {
+        name: "keep artifacts",
+        argument: Argument::None,
+        description: "Turn keeping artifacts of failed jobs on or off",
+        messages: |_, context| vec![Message::ToggleKeepArtifacts(!context.keep_artifacts)],
+    },
+    Command {
+        name: "exit",
+        argument: Argument::None,
+        description: "Close Win tool box",
+        messages: |_, _| vec![Message::ControlMenuBtn(ControlMenuVariations::ExitProgramm)],
+    },
+];
+
+/// Suggestions for palette input starting with `>`, best first. Commands are matched
+/// fuzzy until their name and a space are typed, then their arguments are
+pub fn suggestions(input: &str, context: &Context) -> Vec<Suggestion> {
+    let input = input.trim_start_matches('>').trim_start();
+    let lower = input.to_lowercase();
+    let rest = |command: &Command| {
+        lower
+            .strip_prefix(command.name)
+            .filter(|rest| command.argument != Argument::None && rest.starts_with(' '))
+            .and_then(|_| input.get(command.name.len() + 1..))
+    };
+    // Once a command with an argument is typed, other commands only show when their
+    // name goes on with the input, like "upgrade all" for "upgrade a"
+    let typed_command = COMMANDS.iter().any(|command| rest(command).is_some());
+    let mut found = Vec::new();
+    for command in COMMANDS {
+        if let Some(rest) = rest(command) {
+            found.extend(arguments(command, rest, context));
+            continue;
+        }
+        if typed_command && !command.name.starts_with(lower.trim_end()) {
+            continue;
+        }
+        let Some(score) = fuzzy_score(&lower, command.name) else {
+            continue;
+        };
+        found.push(match command.argument {
+            Argument::None => Suggestion {
+                label: format!(">{}", command.name),
+                description: command.description,
+                completion: format!(">{}", command.name),
+                messages: (command.messages)("", context),
+                score,
+            },
+            _ => Suggestion {
+                label: format!(">{} …", command.name),
+                description: command.description,
+                completion: format!(">{} ", command.name),
+                messages: Vec::new(),
+                score,
+            },
+        });
+    }
+    found.sort_by_key(|suggestion| Reverse(suggestion.score));
+    found
+}
+
+fn arguments(command: &Command, rest: &str, context: &Context) -> Vec<Suggestion> {
+    let suggestion = |argument: &str, score| {
+        let label = format!(">{} {}", command.name, argument);
+        Suggestion {
+            completion: label.clone(),
+            label,
+            description: command.description,
+            messages: (command.messages)(argument, context),
+            score,
+        }
+    };
+    let lower = rest.to_lowercase();
+    let by_name = |names: Vec<String>| -> Vec<Suggestion> {
+        names
+            .iter()
+            .filter_map(|name| {
+                fuzzy_score(&lower, name).map(|score| suggestion(name.as_str(), score))
+            })
+            .collect()
+    };
+    match command.argument {
+        Argument::None => Vec::new(),
+        // Programms the command can't do anything with aren't offered
+        Argument::Programm => search::rank(context.programms, context.docs, rest)
+            .into_iter()
+            .map(|result| suggestion(&result.name, result.score))
+            .filter(|suggestion| !suggestion.messages.is_empty())
+            .collect(),
+        Argument::Config => by_name(config_files(Path::new("."))),
+        Argument::Menu => by_name(MENUS.iter().map(|(name, _)| name.to_string()).collect()),
+        Argument::Template => by_name(TEMPLATES.iter().map(|t| t.id.to_string()).collect()),
+        Argument::Theme => by_name(context.themes.iter().map(|t| t.name.clone()).collect()),
+    }
+}
+
+/// Everything matches empty input
+fn fuzzy_score(input: &str, text: &str) -> Option<i64> {
+    let pattern: Vec<char> = input.chars().filter(|c| !c.is_whitespace()).collect();
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    search::fuzzy_match(&pattern, text).map(|(score, _)| score)
+}
+
+/// JSON files in the directory
+fn config_files(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".json"))
+        .collect();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProgrammStatus;
+
+    fn programms() -> BTreeMap<String, Programm> {
+        [
+            ("git", ProgrammStatus::Installed),
+            ("node", ProgrammStatus::NotInstalled),
+            ("neovim", ProgrammStatus::Installing),
+        ]
+        .into_iter()
+        .map(|(name, status)| {
+            let prog = Programm {
+                name: name.to_string(),
+                status,
+                ..Default::default()
+            };
+            (name.to_string(), prog)
+        })
+        .collect()
+    }
+
+    fn labels(input: &str) -> Vec<String> {
+        let programms = programms();
+        let docs = DocsIndex::build(&programms);
+        let context = Context {
+            programms: &programms,
+            docs: &docs,
+            dry_run: false,
+            keep_artifacts: false,
+            themes: &[],
+        };
+        suggestions(input, &context)
+            .into_iter()
+            .map(|suggestion| suggestion.label)
+            .collect()
+    }
+
+    #[test]
+    fn commands_are_matched_fuzzy_and_ranked() {
+        let found = labels(">upgr");
+        assert_eq!(found[0], ">upgrade …");
+        assert!(found.contains(&">upgrade all".to_string()));
+        assert!(!found.contains(&">install …".to_string()));
+        assert_eq!(labels(">chkupd")[0], ">check updates");
+        assert!(labels(">qqq").is_empty());
+        // Empty input lists every command
+        assert_eq!(labels(">").len(), COMMANDS.len());
+    }
+
+    #[test]
+    fn typed_command_lists_only_its_arguments() {
+        assert_eq!(labels(">open hist"), [">open history"]);
+        // Other commands stay only while their name goes on with the input
+        assert_eq!(labels(">upgrade a"), [">upgrade all"]);
+    }
+
+    #[test]
+    fn manipulations_follow_programm_status() {
+        assert_eq!(labels(">install "), [">install node"]);
+        assert_eq!(labels(">uninstall "), [">uninstall git"]);
+        // Programm with a running job can't be manipulated at all, but still run
+        assert!(!labels(">reinstall ").contains(&">reinstall neovim".to_string()));
+        assert!(labels(">run ").contains(&">run neovim".to_string()));
+    }
+}
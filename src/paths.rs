@@ -0,0 +1,71 @@
+use std::{
+    env,
+    path::{Component, Path, PathBuf},
+};
+
+const APP_DIR: &str = "wtb";
+
+fn home_dir() -> PathBuf {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// `%LOCALAPPDATA%\wtb\<windows_subdir>` on Windows,
+/// `$<xdg_var>/wtb` (or `~/<xdg_fallback>/wtb`) elsewhere
+fn app_dir(xdg_var: &str, xdg_fallback: &str, windows_subdir: &str) -> PathBuf {
+    if cfg!(windows) {
+        let local = env::var_os("LOCALAPPDATA")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home_dir().join("AppData").join("Local"));
+        return local.join(APP_DIR).join(windows_subdir);
+    }
+    env::var_os(xdg_var)
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home_dir().join(xdg_fallback))
+        .join(APP_DIR)
+}
+
+/// `%APPDATA%\wtb` on Windows, `$XDG_CONFIG_HOME/wtb` (or `~/.config/wtb`) elsewhere
+pub fn config_dir() -> PathBuf {
+    if cfg!(windows) {
+        if let Some(dir) = env::var_os("APPDATA") {
+            return PathBuf::from(dir).join(APP_DIR);
+        }
+    }
+    app_dir("XDG_CONFIG_HOME", ".config", "config")
+}
+
+/// Downloaded artifacts, safe to delete at any time
+pub fn cache_dir() -> PathBuf {
+    app_dir("XDG_CACHE_HOME", ".cache", "cache")
+}
+
+/// Installed portable apps, their manifests and shims
+pub fn data_dir() -> PathBuf {
+    app_dir("XDG_DATA_HOME", ".local/share", "data")
+}
+
+pub fn apps_dir() -> PathBuf {
+    data_dir().join("apps")
+}
+
+/// Temporary working directories of jobs, one per run
+pub fn jobs_dir() -> PathBuf {
+    env::temp_dir().join(APP_DIR)
+}
+
+/// Single directory with shims of all portable apps, the only one which needs to be in PATH
+pub fn bin_dir() -> PathBuf {
+    data_dir().join("bin")
+}
+
+/// Whether the name is a single plain path component, so joining it can't leave the directory
+pub fn is_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    !name.contains(['/', '\\'])
+        && matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none()
+}
@@ -0,0 +1,477 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    fmt,
+    io::Read,
+    path::Path,
+    process,
+    sync::RwLock,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Exit codes Windows installers use for "success, reboot required" and "reboot started"
+const DEFAULT_REBOOT_EXIT_CODES: [i32; 2] = [3010, 1641];
+
+/// Shell chosen in the settings, empty for the platform one
+static SHELL: RwLock<String> = RwLock::new(String::new());
+
+/// Script of a programm, written either as a plain string or as
+/// `{ "run": "...", "timeout_secs": 600, "retries": 2, ... }`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(from = "ScriptDef", into = "ScriptDef")]
+pub struct Script {
+    pub run: String,
+    pub policy: RunPolicy,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum ScriptDef {
+    Plain(String),
+    Detailed {
+        run: String,
+        #[serde(flatten)]
+        policy: RunPolicy,
+    },
+}
+
+/// How a script or installer is run: time limit, retries and exit codes counted as success
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RunPolicy {
+    /// Process tree is killed when it runs longer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// Extra attempts after a failure or timeout
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub retries: u32,
+    /// Seconds between attempts
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub retry_delay: u32,
+    /// Exit codes besides 0 meaning success
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub success_exit_codes: Vec<i32>,
+    /// Exit codes meaning success which needs a reboot to finish
+    #[serde(
+        default = "default_reboot_exit_codes",
+        skip_serializing_if = "is_default_reboot_exit_codes"
+    )]
+    pub reboot_exit_codes: Vec<i32>,
+}
+
+/// Process started by `output` while `capture` is on
+#[derive(Debug, Clone)]
+pub struct RunRecord {
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    pub stdout: String,
+    pub stderr: String,
+    /// Process couldn't be started or timed out
+    pub error: Option<String>,
+}
+
+thread_local! {
+    static CAPTURED: RefCell<Option<Vec<RunRecord>>> = const { RefCell::new(None) };
+}
+
+/// How a successful run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Completion {
+    Done,
+    NeedsReboot,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+fn default_reboot_exit_codes() -> Vec<i32> {
+    DEFAULT_REBOOT_EXIT_CODES.to_vec()
+}
+
+fn is_default_reboot_exit_codes(codes: &Vec<i32>) -> bool {
+    *codes == DEFAULT_REBOOT_EXIT_CODES
+}
+
+impl Default for RunPolicy {
+    fn default() -> Self {
+        RunPolicy {
+            timeout_secs: None,
+            retries: 0,
+            retry_delay: 0,
+            success_exit_codes: Vec::new(),
+            reboot_exit_codes: default_reboot_exit_codes(),
+        }
+    }
+}
+
+impl From<ScriptDef> for Script {
+    fn from(def: ScriptDef) -> Self {
+        match def {
+            ScriptDef::Plain(run) => Script {
+                run,
+                policy: RunPolicy::default(),
+            },
+            ScriptDef::Detailed { run, policy } => Script { run, policy },
+        }
+    }
+}
+
+impl From<Script> for ScriptDef {
+    fn from(script: Script) -> Self {
+        if script.policy == RunPolicy::default() {
+            ScriptDef::Plain(script.run)
+        } else {
+            ScriptDef::Detailed {
+                run: script.run,
+                policy: script.policy,
+            }
+        }
+    }
+}
+
+impl From<String> for Script {
+    fn from(run: String) -> Self {
+        Script {
+            run,
+            policy: RunPolicy::default(),
+        }
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.run, self.policy)
+    }
+}
+
+impl RunPolicy {
+    /// `None` when the exit status means failure
+    pub fn completion(&self, status: process::ExitStatus) -> Option<Completion> {
+        self.completion_of(status.code())
+    }
+
+    /// Same for an exit code, `None` when killed by a signal
+    fn completion_of(&self, code: Option<i32>) -> Option<Completion> {
+        match code {
+            Some(0) => Some(Completion::Done),
+            Some(code) if self.reboot_exit_codes.contains(&code) => Some(Completion::NeedsReboot),
+            Some(code) if self.success_exit_codes.contains(&code) => Some(Completion::Done),
+            _ => None,
+        }
+    }
+}
+
+/// Non-default settings in parentheses, nothing for the default policy
+impl fmt::Display for RunPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(timeout) = self.timeout_secs {
+            parts.push(format!("timeout {} s", timeout));
+        }
+        if self.retries > 0 {
+            parts.push(format!(
+                "{} retries {} s apart",
+                self.retries, self.retry_delay
+            ));
+        }
+        let codes = |codes: &[i32]| {
+            codes
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        if !self.success_exit_codes.is_empty() {
+            parts.push(format!(
+                "also succeeds with {}",
+                codes(&self.success_exit_codes)
+            ));
+        }
+        if !is_default_reboot_exit_codes(&self.reboot_exit_codes) {
+            parts.push(format!(
+                "reboot needed on {}",
+                codes(&self.reboot_exit_codes)
+            ));
+        }
+        if parts.is_empty() {
+            Ok(())
+        } else {
+            write!(f, " ({})", parts.join(", "))
+        }
+    }
+}
+
+/// Runs the command under the policy and collects its output. Failed and timed out
+/// attempts are retried, the output of the last attempt is returned either way
+pub fn output(
+    command: &mut process::Command,
+    policy: &RunPolicy,
+) -> Result<process::Output, String> {
+    let mut attempt = 0;
+    loop {
+        let started = Instant::now();
+        let result = run_once(command, policy.timeout_secs.map(Duration::from_secs));
+        remember(command, &result, started.elapsed());
+        let succeeded = matches!(&result, Ok(output) if policy.completion(output.status).is_some());
+        if succeeded || attempt >= policy.retries {
+            return result;
+        }
+        attempt += 1;
+        thread::sleep(Duration::from_secs(policy.retry_delay.into()));
+    }
+}
+
+pub fn set_shell(shell: &str) {
+    match SHELL.write() {
+        Ok(mut current) => *current = shell.trim().to_string(),
+        Err(e) => *e.into_inner() = shell.trim().to_string(),
+    }
+}
+
+/// Program and arguments running the script with the chosen shell, `pwsh` on Windows
+/// and `sh` elsewhere unless the settings say otherwise
+pub fn shell_command(script: &str) -> (String, Vec<String>) {
+    let chosen = SHELL
+        .read()
+        .map_or_else(|e| e.into_inner().clone(), |s| s.clone());
+    let program = match chosen.as_str() {
+        "" if cfg!(windows) => "pwsh".to_string(),
+        "" => "sh".to_string(),
+        other => other.to_string(),
+    };
+    let name = Path::new(&program)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let mut args: Vec<String> = match name.as_str() {
+        "pwsh" | "powershell" => vec!["-NoProfile".to_string(), "-Command".to_string()],
+        "cmd" => vec!["/C".to_string()],
+        _ => vec!["-c".to_string()],
+    };
+    args.push(script.to_string());
+    (program, args)
+}
+
+/// Runs `f` recording every process it starts through `output` on this thread
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<RunRecord>) {
+    let outer = CAPTURED.with(|captured| captured.replace(Some(Vec::new())));
+    let result = f();
+    let runs = CAPTURED
+        .with(|captured| captured.replace(outer))
+        .unwrap_or_default();
+    (result, runs)
+}
+
+fn remember(
+    command: &process::Command,
+    result: &Result<process::Output, String>,
+    duration: Duration,
+) {
+    CAPTURED.with(|captured| {
+        let mut captured = captured.borrow_mut();
+        let Some(runs) = captured.as_mut() else {
+            return;
+        };
+        let mut line = command.get_program().to_string_lossy().to_string();
+        for arg in command.get_args() {
+            let arg = arg.to_string_lossy();
+            if arg.contains(' ') {
+                line += &format!(" \"{}\"", arg);
+            } else {
+                line += &format!(" {}", arg);
+            }
+        }
+        let (exit_code, stdout, stderr, error) = match result {
+            Ok(output) => (
+                output.status.code(),
+                String::from_utf8_lossy(&output.stdout).to_string(),
+                String::from_utf8_lossy(&output.stderr).to_string(),
+                None,
+            ),
+            Err(e) => (None, String::new(), String::new(), Some(e.clone())),
+        };
+        runs.push(RunRecord {
+            command: line,
+            exit_code,
+            duration_ms: duration.as_millis() as u64,
+            stdout,
+            stderr,
+            error,
+        });
+    });
+}
+
+fn run_once(
+    command: &mut process::Command,
+    timeout: Option<Duration>,
+) -> Result<process::Output, String> {
+    let program = command.get_program().to_string_lossy().to_string();
+    crate::env::apply_to(command);
+    // Own process group on unix, so the whole tree can be killed on timeout. Only then,
+    // a background group can't ask for a sudo password in the terminal
+    #[cfg(unix)]
+    if timeout.is_some() {
+        std::os::unix::process::CommandExt::process_group(command, 0);
+    }
+    let mut child = command
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Can't run {}: {}", program, e))?;
+    // Pipes are drained while waiting, a full pipe would block the child forever
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let wait_error = |e: std::io::Error| format!("Can't wait for {}: {}", program, e);
+    let status = match timeout {
+        None => child.wait().map_err(wait_error)?,
+        Some(timeout) => {
+            let deadline = Instant::now() + timeout;
+            loop {
+                if let Some(status) = child.try_wait().map_err(wait_error)? {
+                    break status;
+                }
+                if Instant::now() >= deadline {
+                    kill_tree(&mut child);
+                    let _ = child.wait();
+                    return Err(format!(
+                        "{} timed out after {} s and was killed",
+                        program,
+                        timeout.as_secs()
+                    ));
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    };
+    Ok(process::Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut data = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut data);
+        }
+        data
+    })
+}
+
+/// Kills the child with everything it started, windows opened by scripts included
+fn kill_tree(child: &mut process::Child) {
+    let pid = child.id().to_string();
+    let killed = if cfg!(windows) {
+        process::Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid])
+            .output()
+    } else {
+        process::Command::new("kill")
+            .args(["-KILL", "--", &format!("-{}", pid)])
+            .output()
+    };
+    if !killed.is_ok_and(|output| output.status.success()) {
+        let _ = child.kill();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{env, fs, path::PathBuf};
+
+    fn sh(script: &str) -> process::Command {
+        let mut command = process::Command::new("sh");
+        command.args(["-c", script]);
+        command
+    }
+
+    fn temp_file(test: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("wtb-script-{}-{}", test, process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn timeout_kills_the_process_tree() {
+        let pid_file = temp_file("timeout");
+        let policy = RunPolicy {
+            timeout_secs: Some(1),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let script = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+        let error = output(&mut sh(&script), &policy).unwrap_err();
+        assert!(error.contains("timed out after 1 s"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(10));
+        // Background sleep went with its parent, it may stay a zombie without a reaper
+        let pid = fs::read_to_string(&pid_file).unwrap();
+        thread::sleep(Duration::from_millis(200));
+        let state = process::Command::new("ps")
+            .args(["-o", "stat=", "-p", pid.trim()])
+            .output()
+            .unwrap();
+        let state = String::from_utf8_lossy(&state.stdout);
+        assert!(
+            state.trim().is_empty() || state.starts_with('Z'),
+            "{} is still running",
+            pid.trim()
+        );
+    }
+
+    #[test]
+    fn failed_attempts_are_retried() {
+        let counter = temp_file("retries");
+        let policy = RunPolicy {
+            retries: 2,
+            ..Default::default()
+        };
+        let script = format!("echo run >> {}; exit 1", counter.display());
+        let result = output(&mut sh(&script), &policy).unwrap();
+        assert_eq!(result.status.code(), Some(1));
+        assert_eq!(fs::read_to_string(&counter).unwrap().lines().count(), 3);
+
+        // Success ends the attempts
+        fs::remove_file(&counter).unwrap();
+        let script = format!(
+            "echo run >> {0}; test $(wc -l < {0}) -ge 2",
+            counter.display()
+        );
+        let result = output(&mut sh(&script), &policy).unwrap();
+        assert!(result.status.success());
+        assert_eq!(fs::read_to_string(&counter).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn success_exit_codes_count_as_success() {
+        let policy = RunPolicy {
+            success_exit_codes: vec![7],
+            ..Default::default()
+        };
+        let status = output(&mut sh("exit 7"), &policy).unwrap().status;
+        assert_eq!(policy.completion(status), Some(Completion::Done));
+        assert_eq!(RunPolicy::default().completion(status), None);
+        let status = output(&mut sh("exit 8"), &policy).unwrap().status;
+        assert_eq!(policy.completion(status), None);
+    }
+
+    #[test]
+    fn reboot_exit_codes_need_a_reboot() {
+        let policy: RunPolicy = serde_json::from_str("{}").unwrap();
+        assert_eq!(policy, RunPolicy::default());
+        for code in [3010, 1641] {
+            assert_eq!(
+                policy.completion_of(Some(code)),
+                Some(Completion::NeedsReboot)
+            );
+        }
+        assert_eq!(policy.completion_of(Some(0)), Some(Completion::Done));
+        assert_eq!(policy.completion_of(Some(1)), None);
+        assert_eq!(policy.completion_of(None), None);
+    }
+}
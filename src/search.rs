@@ -0,0 +1,231 @@
+use crate::{
+    index::{DocsIndex, Snippet},
+    Programm,
+};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+};
+
+/// Every matched character
+const MATCH_SCORE: i64 = 16;
+/// Match right after the previous one
+const CONSECUTIVE_BONUS: i64 = 16;
+/// Match at the start of the text or of a word in it, e.g. "c" in "llvm-clang"
+const WORD_START_BONUS: i64 = 12;
+const PREFIX_BONUS: i64 = 24;
+/// Most a gap between matched characters costs
+const MAX_GAP_PENALTY: i64 = 8;
+/// Tag matches rank below name matches, description matches below both
+const TAG_DIVISOR: i64 = 2;
+const DESCRIPTION_SCORE: i64 = 1;
+
+/// Where the query matched the programm
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchedIn {
+    Name,
+    Tag(String),
+    /// Full-text match with the index score
+    Description(usize, Snippet),
+}
+
+/// Programm found by `rank`
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub name: String,
+    pub score: i64,
+    /// Char indices of the name matched by the query, for highlighting
+    pub positions: Vec<usize>,
+    pub matched_in: MatchedIn,
+}
+
+/// Programms matching the query, best first and by name on equal scores, so the
+/// order only changes with the query. Every programm matches an empty query
+pub fn rank(
+    programms: &BTreeMap<String, Programm>,
+    docs: &DocsIndex,
+    query: &str,
+) -> Vec<SearchResult> {
+    let query = query.trim().to_lowercase();
+    let pattern: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut doc_hits: HashMap<String, _> = docs
+        .search(&query)
+        .into_iter()
+        .map(|hit| (hit.programm, (hit.score, hit.snippet)))
+        .collect();
+    let mut results: Vec<SearchResult> = programms
+        .values()
+        .filter_map(|prog| {
+            if pattern.is_empty() {
+                return Some(SearchResult {
+                    name: prog.name.clone(),
+                    score: 0,
+                    positions: Vec::new(),
+                    matched_in: MatchedIn::Name,
+                });
+            }
+            let name = fuzzy_match(&pattern, &prog.name).map(|(score, positions)| SearchResult {
+                name: prog.name.clone(),
+                score,
+                positions,
+                matched_in: MatchedIn::Name,
+            });
+            let tag = prog
+                .tags
+                .iter()
+                .filter_map(|tag| {
+                    fuzzy_match(&pattern, tag).map(|(score, _)| SearchResult {
+                        name: prog.name.clone(),
+                        score: score / TAG_DIVISOR,
+                        positions: Vec::new(),
+                        matched_in: MatchedIn::Tag(tag.clone()),
+                    })
+                })
+                .max_by_key(|result| result.score);
+            let description = doc_hits
+                .remove(&prog.name)
+                .map(|(score, snippet)| SearchResult {
+                    name: prog.name.clone(),
+                    score: DESCRIPTION_SCORE,
+                    positions: Vec::new(),
+                    matched_in: MatchedIn::Description(score, snippet),
+                });
+            [name, tag, description]
+                .into_iter()
+                .flatten()
+                .reduce(|best, result| {
+                    if result.score > best.score {
+                        result
+                    } else {
+                        best
+                    }
+                })
+        })
+        .collect();
+    // Stable sort keeps the name order of the map for equal scores,
+    // description matches are ordered by the index score among themselves
+    results.sort_by_key(|result| {
+        let doc_score = match &result.matched_in {
+            MatchedIn::Description(score, _) => *score,
+            _ => 0,
+        };
+        Reverse((result.score, doc_score))
+    });
+    results
+}
+
+/// Score and matched char indices when all pattern characters appear in the text in order,
+/// case-insensitive. Pattern must be lowercase
+pub fn fuzzy_match(pattern: &[char], text: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = chars.iter().map(|c| lowercase(*c)).collect();
+    let first = *pattern.first()?;
+    // Greedy matching from every place the first character appears, the best one wins
+    (0..lower.len())
+        .filter(|&start| lower[start] == first)
+        .filter_map(|start| {
+            let mut positions = vec![start];
+            let mut next = start + 1;
+            for &p in &pattern[1..] {
+                let found = (next..lower.len()).find(|&i| lower[i] == p)?;
+                positions.push(found);
+                next = found + 1;
+            }
+            Some((score(&chars, &positions), positions))
+        })
+        .fold(
+            None,
+            |best: Option<(i64, Vec<usize>)>, candidate| match best {
+                Some(best) if best.0 >= candidate.0 => Some(best),
+                _ => Some(candidate),
+            },
+        )
+}
+
+fn score(chars: &[char], positions: &[usize]) -> i64 {
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    for &i in positions {
+        score += MATCH_SCORE;
+        if i == 0 {
+            score += PREFIX_BONUS;
+        }
+        if is_word_start(chars, i) {
+            score += WORD_START_BONUS;
+        }
+        match previous {
+            Some(p) if p + 1 == i => score += CONSECUTIVE_BONUS,
+            Some(p) => score -= ((i - p - 1) as i64).min(MAX_GAP_PENALTY),
+            None => score -= (i as i64).min(MAX_GAP_PENALTY),
+        }
+        previous = Some(i);
+    }
+    // Shorter texts win among equal matches
+    score - ((chars.len() - positions.len()) as i64).min(MAX_GAP_PENALTY)
+}
+
+fn is_word_start(chars: &[char], i: usize) -> bool {
+    match i.checked_sub(1).map(|p| chars[p]) {
+        None => true,
+        Some(prev) => !prev.is_alphanumeric() || (prev.is_lowercase() && chars[i].is_uppercase()),
+    }
+}
+
+/// Single char lowercase, keeps char indices of the text and its lowercase form equal
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(query: &str) -> Vec<char> {
+        query.chars().collect()
+    }
+
+    fn programms(names: &[&str]) -> BTreeMap<String, Programm> {
+        names
+            .iter()
+            .map(|name| {
+                let prog = Programm {
+                    name: name.to_string(),
+                    ..Default::default()
+                };
+                (name.to_string(), prog)
+            })
+            .collect()
+    }
+
+    fn names(results: &[SearchResult]) -> Vec<&str> {
+        results.iter().map(|r| r.name.as_str()).collect()
+    }
+
+    #[test]
+    fn word_inside_name_is_found() {
+        let (_, positions) = fuzzy_match(&pattern("clang"), "llvm-clang").unwrap();
+        assert_eq!(positions, vec![5, 6, 7, 8, 9]);
+        let progs = programms(&["llvm-clang", "rustc", "scala-lang"]);
+        let results = rank(&progs, &DocsIndex::default(), "clang");
+        assert_eq!(names(&results), vec!["llvm-clang", "scala-lang"]);
+        assert_eq!(results[0].positions, vec![5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn prefix_beats_gap() {
+        let (prefix, _) = fuzzy_match(&pattern("zig"), "zig-tools").unwrap();
+        let (gap, _) = fuzzy_match(&pattern("zig"), "zxixgtool").unwrap();
+        assert!(prefix > gap);
+        assert!(fuzzy_match(&pattern("zig"), "gzi").is_none());
+    }
+
+    #[test]
+    fn equal_scores_keep_name_order() {
+        let progs = programms(&["c-tool", "a-tool", "b-tool"]);
+        let results = rank(&progs, &DocsIndex::default(), "tool");
+        assert!(results.iter().all(|r| r.score == results[0].score));
+        assert_eq!(names(&results), vec!["a-tool", "b-tool", "c-tool"]);
+        let results = rank(&progs, &DocsIndex::default(), " ");
+        assert_eq!(names(&results), vec!["a-tool", "b-tool", "c-tool"]);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{
+    grouping::{Grouping, SortOrder},
+    paths, ControlMenuVariations,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fmt, fs,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Preferences kept between runs, changed in the settings menu
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Config loaded on start, relative to the working directory
+    pub default_config: PathBuf,
+    /// Name of a built-in or user theme
+    pub theme: String,
+    /// Program scripts are run with, empty for pwsh on Windows and sh elsewhere
+    pub shell: String,
+    /// Jobs running at the same time, the rest wait in the queue
+    pub concurrency: usize,
+    /// Dry run is on when the app starts
+    pub dry_run: bool,
+    /// Job logs are deleted on start after that many days, 0 keeps them
+    pub log_retention_days: u32,
+    /// Window size and position are saved on exit
+    pub remember_window: bool,
+    /// Size and position of the window, a centered default one opens without it
+    pub window: Option<WindowGeometry>,
+    pub ui: UiState,
+}
+
+/// What was on screen when the app was closed, restored on start
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UiState {
+    pub menu: ControlMenuVariations,
+    pub selected_programm: Option<String>,
+    /// Relative offset of the programms list, 0 is the top and 1 the bottom
+    pub list_scroll: f32,
+    pub history_text: String,
+    pub history_action: String,
+    pub history_result: String,
+    pub sort: SortOrder,
+    pub grouping: Grouping,
+    /// Categories folded in the programms list
+    pub collapsed: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct WindowGeometry {
+    pub width: f32,
+    pub height: f32,
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            default_config: PathBuf::from("programms.json"),
+            theme: String::new(),
+            shell: String::new(),
+            concurrency: 1,
+            dry_run: false,
+            log_retention_days: 0,
+            remember_window: true,
+            window: None,
+            ui: UiState::default(),
+        }
+    }
+}
+
+pub const CONCURRENCY_CHOICES: [usize; 4] = [1, 2, 3, 4];
+
+pub const RETENTION_CHOICES: [Retention; 5] = [
+    Retention(0),
+    Retention(7),
+    Retention(30),
+    Retention(90),
+    Retention(365),
+];
+
+/// Days job logs are kept, shown in the settings menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention(pub u32);
+
+impl fmt::Display for Retention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            0 => f.write_str("forever"),
+            1 => f.write_str("1 day"),
+            days => write!(f, "{} days", days),
+        }
+    }
+}
+
+/// Settings file couldn't be read nor backed up, so it must not be overwritten
+static BROKEN: AtomicBool = AtomicBool::new(false);
+
+pub fn settings_path() -> PathBuf {
+    paths::config_dir().join("settings.json")
+}
+
+impl Settings {
+    /// Settings from the file, defaults until something is changed
+    /// A file which can't be read is copied to `settings.json.bak` first, so saving the
+    /// defaults over it later doesn't lose what the user wrote
+    pub fn load() -> Result<Settings, String> {
+        let path = settings_path();
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(_) => return Ok(Settings::default()),
+        };
+        serde_json::from_str(&data).map_err(|e| {
+            let backup = path.with_extension("json.bak");
+            match fs::copy(&path, &backup) {
+                Ok(_) => format!(
+                    "Can't read {}: {}, it is kept as {}",
+                    path.display(),
+                    e,
+                    backup.display()
+                ),
+                Err(copy) => {
+                    BROKEN.store(true, Ordering::Relaxed);
+                    format!(
+                        "Can't read {}: {}, it isn't changed until fixed ({})",
+                        path.display(),
+                        e,
+                        copy
+                    )
+                }
+            }
+        })
+    }
+
+    /// Written to a temporary file first, so a crash never leaves half a file
+    pub fn save(&self) -> Result<(), String> {
+        let path = settings_path();
+        if BROKEN.load(Ordering::Relaxed) {
+            return Err(format!(
+                "{} can't be read, fix or delete it to save settings",
+                path.display()
+            ));
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let data = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, data)
+            .and_then(|()| fs::rename(&tmp_path, &path))
+            .map_err(|e| format!("Can't write {}: {}", path.display(), e))
+    }
+}
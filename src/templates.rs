@@ -0,0 +1,117 @@
+use crate::{paths, ConfigLoad};
+use std::{
+    error::Error,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Built-in catalog, shipped inside the binary, to start a new config from.
+pub struct Template {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    source: &'static str,
+}
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        id: "windows-admin",
+        title: "Windows admin",
+        description: "MMC, regedit, event viewer, PowerShell 7 and Sysinternals",
+        source: include_str!("../templates/windows-admin.json"),
+    },
+    Template {
+        id: "rust-dev",
+        title: "Rust dev",
+        description: "rustup, stable toolchain, clippy and rust-analyzer",
+        source: include_str!("../templates/rust-dev.json"),
+    },
+    Template {
+        id: "python-dev",
+        title: "Python dev",
+        description: "Python 3.12, pipx and ruff",
+        source: include_str!("../templates/python-dev.json"),
+    },
+    Template {
+        id: "linux-workstation",
+        title: "Linux workstation",
+        description: "git, htop, curl and build tools from apt",
+        source: include_str!("../templates/linux-workstation.json"),
+    },
+];
+
+pub fn find_template(id: &str) -> Option<&'static Template> {
+    TEMPLATES.iter().find(|t| t.id == id)
+}
+
+impl Template {
+    pub fn config(&self) -> Result<ConfigLoad, serde_json::Error> {
+        serde_json::from_str(self.source)
+    }
+
+    /// Where the menu creates the config, in the app config directory
+    pub fn config_path(&self) -> PathBuf {
+        paths::config_dir().join(format!("{}.json", self.id))
+    }
+
+    /// Writes the template to `path`, never overwriting an existing file.
+    pub fn instantiate(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.config()?;
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Can't create {}: {}", dir.display(), e))?;
+        }
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| format!("Can't create {}: {}", path.display(), e))?;
+        file.write_all(self.source.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the template to `path` unless the file is there already, an earlier
+    /// copy may have been edited since. Returns whether the file was written
+    pub fn instantiate_once(&self, path: &Path) -> Result<bool, Box<dyn Error>> {
+        if path.exists() {
+            return Ok(false);
+        }
+        self.instantiate(path).map(|()| true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_parse() {
+        for template in TEMPLATES {
+            let config = template
+                .config()
+                .unwrap_or_else(|e| panic!("{}: {}", template.id, e));
+            assert!(!config.programms.is_empty(), "{} is empty", template.id);
+        }
+    }
+
+    #[test]
+    fn existing_copy_is_kept() {
+        let dir = std::env::temp_dir().join(format!("wtb-templates-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let template = find_template("rust-dev").unwrap();
+        let path = dir.join("configs").join("rust-dev.json");
+        assert!(template.instantiate_once(&path).unwrap());
+        fs::write(&path, "{\"name\": \"edited\", \"programms\": []}").unwrap();
+        assert!(!template.instantiate_once(&path).unwrap());
+        assert!(fs::read_to_string(&path).unwrap().contains("edited"));
+        assert!(template.instantiate(&path).is_err());
+    }
+
+    #[test]
+    fn template_ids_are_unique() {
+        for template in TEMPLATES {
+            assert_eq!(find_template(template.id).unwrap().title, template.title);
+        }
+    }
+}
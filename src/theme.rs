@@ -0,0 +1,371 @@
+use crate::{paths, ProgrammStatus};
+use iced::{theme::Palette, Color, Theme};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    path::PathBuf,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+/// Every color of the UI. Theme files may leave colors out, dark theme ones are used then
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AppTheme {
+    pub name: String,
+    pub background: Hex,
+    pub text: Hex,
+    /// Buttons, checkboxes and inputs drawn by iced itself
+    pub primary: Hex,
+    pub panel: Hex,
+    pub panel_border: Hex,
+    pub button: Hex,
+    pub button_hovered: Hex,
+    pub button_text: Hex,
+    /// Dims the menu under the search and dialogs, usually semi-transparent
+    pub overlay: Hex,
+    pub error: Hex,
+    pub success: Hex,
+    pub info: Hex,
+    /// Keyboard focus and matched search characters
+    pub highlight: Hex,
+    pub code: Hex,
+    pub code_background: Hex,
+    pub link: Hex,
+    pub status_text: Hex,
+    pub statuses: StatusColors,
+}
+
+/// Background of programm buttons by status, normal and hovered
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct StatusColors {
+    pub installed: [Hex; 2],
+    pub not_installed: [Hex; 2],
+    pub failed: [Hex; 2],
+    pub broken: [Hex; 2],
+    pub outdated: [Hex; 2],
+    /// Detecting, installing and uninstalling
+    pub working: [Hex; 2],
+    pub unknown: [Hex; 2],
+    pub unsupported: [Hex; 2],
+    pub needs_reboot: [Hex; 2],
+}
+
+/// Color written as `#RRGGBB` or `#RRGGBBAA` in theme files
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Hex(pub Color);
+
+impl TryFrom<String> for Hex {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let digits = text.trim_start_matches('#');
+        let channel = |i: usize| {
+            digits
+                .get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                .ok_or_else(|| format!("\"{}\" is not a #RRGGBB or #RRGGBBAA color", text))
+        };
+        let alpha = match digits.len() {
+            6 => 255,
+            8 => channel(6)?,
+            _ => return Err(format!("\"{}\" is not a #RRGGBB or #RRGGBBAA color", text)),
+        };
+        Ok(Hex(Color::from_rgba8(
+            channel(0)?,
+            channel(2)?,
+            channel(4)?,
+            alpha as f32 / 255.0,
+        )))
+    }
+}
+
+impl From<Hex> for String {
+    fn from(hex: Hex) -> Self {
+        hex.to_string()
+    }
+}
+
+impl fmt::Display for Hex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b, a] = self.0.into_rgba8();
+        if a == 255 {
+            write!(f, "#{:02X}{:02X}{:02X}", r, g, b)
+        } else {
+            write!(f, "#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+        }
+    }
+}
+
+/// Color of a built-in theme, a typo fails the tests instead of turning black
+fn hex(text: &str) -> Hex {
+    let parsed = Hex::try_from(text.to_string());
+    debug_assert!(parsed.is_ok(), "{:?}", parsed);
+    parsed.unwrap_or(Hex(Color::BLACK))
+}
+
+fn pair(normal: &str, hovered: &str) -> [Hex; 2] {
+    [hex(normal), hex(hovered)]
+}
+
+impl Default for AppTheme {
+    fn default() -> Self {
+        AppTheme::dark()
+    }
+}
+
+impl Default for StatusColors {
+    fn default() -> Self {
+        AppTheme::dark().statuses
+    }
+}
+
+impl AppTheme {
+    pub fn dark() -> AppTheme {
+        AppTheme {
+            name: "Dark".to_string(),
+            background: hex("#2E3440"),
+            text: hex("#D8DEE9"),
+            primary: hex("#81A1C1"),
+            panel: hex("#343D4B"),
+            panel_border: hex("#4C566A"),
+            button: hex("#4C566A"),
+            button_hovered: hex("#5E81AC"),
+            button_text: hex("#D8DEE9"),
+            overlay: hex("#343D4B99"),
+            error: hex("#BF616A"),
+            success: hex("#A3BE8C"),
+            info: hex("#D8DEE9"),
+            highlight: hex("#EBCB8B"),
+            code: hex("#88C0D0"),
+            code_background: hex("#88C0D0"),
+            link: hex("#81A1C1"),
+            status_text: hex("#E5E9F0"),
+            statuses: StatusColors {
+                installed: pair("#6B825F", "#A3BE8C"),
+                not_installed: pair("#8C464F", "#BF616A"),
+                failed: pair("#7E6379", "#B48EAD"),
+                broken: pair("#9C6554", "#D08770"),
+                outdated: pair("#8F7D56", "#EBCB8B"),
+                working: pair("#4C6A8C", "#5E81AC"),
+                unknown: pair("#4C566A", "#616E88"),
+                unsupported: pair("#3B4252", "#434C5E"),
+                needs_reboot: pair("#5B7F86", "#88C0D0"),
+            },
+        }
+    }
+
+    pub fn light() -> AppTheme {
+        AppTheme {
+            name: "Light".to_string(),
+            background: hex("#ECEFF4"),
+            text: hex("#2E3440"),
+            primary: hex("#5E81AC"),
+            panel: hex("#E5E9F0"),
+            panel_border: hex("#C2CAD6"),
+            button: hex("#D8DEE9"),
+            button_hovered: hex("#81A1C1"),
+            button_text: hex("#2E3440"),
+            overlay: hex("#D8DEE999"),
+            error: hex("#BF616A"),
+            success: hex("#5E7F45"),
+            info: hex("#4C566A"),
+            highlight: hex("#5E81AC"),
+            code: hex("#2E3440"),
+            code_background: hex("#D8DEE9"),
+            link: hex("#5E81AC"),
+            status_text: hex("#2E3440"),
+            statuses: StatusColors {
+                installed: pair("#B5CEA0", "#A3BE8C"),
+                not_installed: pair("#E3A8AE", "#D38A91"),
+                failed: pair("#D7BCD2", "#B48EAD"),
+                broken: pair("#EBB9A5", "#D08770"),
+                outdated: pair("#F3DFAE", "#EBCB8B"),
+                working: pair("#A9C0DB", "#81A1C1"),
+                unknown: pair("#D8DEE9", "#C2CAD6"),
+                unsupported: pair("#E5E9F0", "#D8DEE9"),
+                needs_reboot: pair("#B6DCE4", "#88C0D0"),
+            },
+        }
+    }
+
+    pub fn high_contrast() -> AppTheme {
+        AppTheme {
+            name: "High contrast".to_string(),
+            background: hex("#000000"),
+            text: hex("#FFFFFF"),
+            primary: hex("#FFFF00"),
+            panel: hex("#000000"),
+            panel_border: hex("#FFFFFF"),
+            button: hex("#1A1A1A"),
+            button_hovered: hex("#404040"),
+            button_text: hex("#FFFFFF"),
+            overlay: hex("#000000CC"),
+            error: hex("#FF5555"),
+            success: hex("#55FF55"),
+            info: hex("#FFFFFF"),
+            highlight: hex("#FFFF00"),
+            code: hex("#00FFFF"),
+            code_background: hex("#000000"),
+            link: hex("#00FFFF"),
+            status_text: hex("#FFFFFF"),
+            statuses: StatusColors {
+                installed: pair("#006400", "#008000"),
+                not_installed: pair("#8B0000", "#B00000"),
+                failed: pair("#4B0082", "#6A0DAD"),
+                broken: pair("#8B4500", "#A0522D"),
+                outdated: pair("#665C00", "#807000"),
+                working: pair("#00008B", "#0000CD"),
+                unknown: pair("#333333", "#4D4D4D"),
+                unsupported: pair("#000000", "#1A1A1A"),
+                needs_reboot: pair("#005F5F", "#007878"),
+            },
+        }
+    }
+
+    /// Dark theme with Okabe-Ito status colors, told apart with any kind of color blindness
+    pub fn colorblind() -> AppTheme {
+        AppTheme {
+            name: "Colorblind safe".to_string(),
+            error: hex("#D55E00"),
+            success: hex("#56B4E9"),
+            highlight: hex("#F0E442"),
+            status_text: hex("#000000"),
+            statuses: StatusColors {
+                installed: pair("#56B4E9", "#8FCDF0"),
+                not_installed: pair("#D55E00", "#E8803A"),
+                failed: pair("#CC79A7", "#DDA0C4"),
+                broken: pair("#E69F00", "#F2BC4D"),
+                outdated: pair("#F0E442", "#F6EE85"),
+                working: pair("#009E73", "#33B88F"),
+                unknown: pair("#999999", "#B3B3B3"),
+                unsupported: pair("#666666", "#7F7F7F"),
+                needs_reboot: pair("#0072B2", "#338ECB"),
+            },
+            ..AppTheme::dark()
+        }
+    }
+
+    /// Normal and hovered background of a programm button
+    pub fn status(&self, status: ProgrammStatus) -> [Color; 2] {
+        let s = &self.statuses;
+        let [normal, hovered] = match status {
+            ProgrammStatus::Installed => s.installed,
+            ProgrammStatus::NotInstalled => s.not_installed,
+            ProgrammStatus::Failed => s.failed,
+            ProgrammStatus::Broken => s.broken,
+            ProgrammStatus::Outdated => s.outdated,
+            ProgrammStatus::Detecting
+            | ProgrammStatus::Installing
+            | ProgrammStatus::Uninstalling => s.working,
+            ProgrammStatus::Unknown => s.unknown,
+            ProgrammStatus::Unsupported => s.unsupported,
+            ProgrammStatus::NeedsReboot => s.needs_reboot,
+        };
+        [normal.0, hovered.0]
+    }
+
+    /// Theme of widgets iced draws itself
+    pub fn iced(&self) -> Theme {
+        Theme::custom(
+            self.name.clone(),
+            Palette {
+                background: self.background.0,
+                text: self.text.0,
+                primary: self.primary.0,
+                success: self.success.0,
+                danger: self.error.0,
+            },
+        )
+    }
+}
+
+static CURRENT: LazyLock<RwLock<Arc<AppTheme>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(AppTheme::dark())));
+
+/// Theme styles draw with
+pub fn current() -> Arc<AppTheme> {
+    CURRENT
+        .read()
+        .map(|theme| theme.clone())
+        .unwrap_or_else(|e| e.into_inner().clone())
+}
+
+pub fn set(theme: AppTheme) {
+    match CURRENT.write() {
+        Ok(mut current) => *current = Arc::new(theme),
+        Err(e) => *e.into_inner() = Arc::new(theme),
+    }
+}
+
+/// User themes, one JSON file each
+pub fn themes_dir() -> PathBuf {
+    paths::config_dir().join("themes")
+}
+
+/// Built-in themes followed by the user ones, with errors of unreadable theme files
+pub fn available() -> (Vec<AppTheme>, Vec<String>) {
+    let mut themes = vec![
+        AppTheme::dark(),
+        AppTheme::light(),
+        AppTheme::high_contrast(),
+        AppTheme::colorblind(),
+    ];
+    let mut errors = Vec::new();
+    let Ok(entries) = fs::read_dir(themes_dir()) else {
+        return (themes, errors);
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let theme = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_str::<AppTheme>(&data).map_err(|e| e.to_string()));
+        match theme {
+            Ok(mut theme) => {
+                // Without a name the file gets the dark theme one
+                if theme.name == AppTheme::dark().name || theme.name.is_empty() {
+                    theme.name = path
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                }
+                themes.push(theme);
+            }
+            Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    (themes, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_themes_round_trip() {
+        for theme in [
+            AppTheme::dark(),
+            AppTheme::light(),
+            AppTheme::high_contrast(),
+            AppTheme::colorblind(),
+        ] {
+            let json = serde_json::to_string(&theme).unwrap();
+            let read: AppTheme = serde_json::from_str(&json).unwrap();
+            assert_eq!(read, theme, "{}", theme.name);
+        }
+    }
+
+    #[test]
+    fn bad_colors_are_rejected() {
+        for text in ["#12345", "#GG0000", "red", "#1234567"] {
+            assert!(Hex::try_from(text.to_string()).is_err(), "{}", text);
+        }
+        let hex = Hex::try_from("#1A2B3C80".to_string()).unwrap();
+        assert_eq!(hex.to_string(), "#1A2B3C80");
+    }
+}